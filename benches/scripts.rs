@@ -0,0 +1,28 @@
+use rust_practice::Engine;
+use std::fs;
+use std::io;
+use std::time::Instant;
+
+fn main() {
+    bench("array.mp", 1_000_000);
+    // every read of a 100k elements array used to copy it
+    bench("large_array.mp", 100);
+}
+
+fn bench(name: &str, iterations: usize) {
+    let source = fs::read_to_string(format!("./.example/{name}")).expect("Should have been able to read the file");
+    let mut script = Engine::new().compile(&source).unwrap();
+
+    // output would dominate the measurement
+    script.set_output(Box::new(io::sink()));
+    script.set_error_output(Box::new(io::sink()));
+    script.set_seed(0);
+
+    let now = Instant::now();
+
+    for _ in 0..iterations {
+        script.run("main", vec![]).unwrap();
+    }
+
+    println!("{name} x{iterations}: {}ms", now.elapsed().as_millis());
+}
@@ -1,25 +1,44 @@
 use crate::parser::{Node, NodeType};
-use crate::procedure::get_procedures;
+use crate::procedure::ProcedureRegistry;
 use crate::program::{Program, Value};
+use std::sync::Arc;
 
 pub struct Compiler {
-    pub program: Program,
+    pub(crate) program: Program,
+    procedures: Arc<ProcedureRegistry>,
 }
 
 impl Compiler {
-    pub fn new() -> Compiler {
+    pub(crate) fn with_procedures(procedures: Arc<ProcedureRegistry>) -> Compiler {
         Compiler {
             program: Program::new(),
+            procedures,
         }
     }
-    pub fn compile(&mut self, node: Node) -> Result<(), String> {
+    // compiles a part of the program with the same procedures
+    pub(crate) fn nested(&self) -> Compiler {
+        Self::with_procedures(self.procedures.clone())
+    }
+    // resolves the procedure of every EXEC, the program is ready to run after it
+    pub(crate) fn link(&mut self) -> Result<(), String> {
+        self.program.link(&self.procedures)
+    }
+    pub(crate) fn compile(&mut self, node: Node) -> Result<(), String> {
         let node_copy = node.clone();
         let node_type: NodeType = node.node_type;
 
         if node_type == NodeType::Operation {
             let proc_name = node_copy.value.as_str();
-            let procedure = get_procedures(proc_name);
-            let mut sub_compiler = Compiler::new();
+            let registered = self.procedures.get(proc_name)?;
+            let procedure = registered.procedure.clone();
+            // a trailing $TARGET is where the result goes, not an argument
+            let argc = node_copy.params.iter().filter(|param| param.node_type != NodeType::Binding).count();
+
+            if !registered.arity.accepts(argc) {
+                return Err(format!("procedure {proc_name} expects {} arguments, got {argc}", registered.arity));
+            }
+
+            let mut sub_compiler = self.nested();
 
             procedure.compile(&mut sub_compiler, node_copy.clone())?;
 
@@ -31,18 +50,23 @@ impl Compiler {
         self.sub_compile(node_copy)
     }
 
-    pub fn sub_compile(&mut self, node: Node) -> Result<(), String> {
+    pub(crate) fn sub_compile(&mut self, node: Node) -> Result<(), String> {
         let node_copy = node.clone();
         let node_type: NodeType = node.node_type;
 
         let mut from_param: usize = 0;
         if node_type == NodeType::FlowDeclaration {
-            self.program.new_mark(node_copy.value.clone());
+            let args: Vec<&Node> = node_copy.params.iter().take_while(|child| child.node_type != NodeType::Constant).collect();
+
+            self.program.new_mark(node_copy.value.clone(), args.len());
+            from_param = args.len() + 1;
 
-            for child in &node_copy.params {
-                from_param += 1;
-                if child.node_type == NodeType::Constant {
-                    break;
+            // arguments are pushed in order, so the last one is on top of the stack
+            for child in args.into_iter().rev() {
+                // untyped $ARG takes the value as is
+                if child.node_type == NodeType::Variable {
+                    self.program.new_var(child.value.clone());
+                    continue;
                 }
                 self.program.new_exec(child.value.clone(), 1);
                 if child.params.len() != 1 {
@@ -58,15 +82,21 @@ impl Compiler {
         }
 
         if node_type == NodeType::Variable {
-            self.program.new_push(Value::String(node_copy.value.clone()));
+            self.program.new_push(Value::string(node_copy.value.clone()));
         } else if node_type == NodeType::Operation {
             self.program.new_exec(node_copy.value.clone(), node_copy.params.len());
         } else if node_type == NodeType::Constant || node_type == NodeType::String {
-            self.program.new_push(Value::String(node_copy.value.clone()));
+            self.program.new_push(Value::string(node_copy.value.clone()));
         } else if node_type == NodeType::Float {
-            self.program.new_push(Value::Float(node_copy.value.parse::<f64>().unwrap()));
+            let number = node_copy.value.parse::<f64>().map_err(|_| format!("number {} is out of range", node_copy.value))?;
+            self.program.new_push(Value::Float(number));
         } else if node_type == NodeType::Integer {
-            self.program.new_push(Value::Integer(node_copy.value.parse::<i64>().unwrap()));
+            let number = node_copy.value.parse::<i64>().map_err(|_| format!("number {} is out of range", node_copy.value))?;
+            self.program.new_push(Value::Integer(number));
+        } else if node_type == NodeType::FlowLink {
+            self.program.new_push(Value::string(node_copy.value.clone()));
+        } else if node_type == NodeType::Null {
+            self.program.new_push(Value::Null);
         }
 
         Ok(())
@@ -0,0 +1,160 @@
+use crate::engine::RuntimeError;
+use crate::program::{Key, Value};
+use std::collections::BTreeMap;
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::string(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::string(value)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Value::array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<K: Into<Key>, V: Into<Value>> From<BTreeMap<K, V>> for Value {
+    fn from(entries: BTreeMap<K, V>) -> Self {
+        Value::map(entries.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+// None is null
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl From<i64> for Key {
+    fn from(key: i64) -> Self {
+        Key::Integer(key)
+    }
+}
+
+impl From<bool> for Key {
+    fn from(key: bool) -> Self {
+        Key::Boolean(key)
+    }
+}
+
+impl From<&str> for Key {
+    fn from(key: &str) -> Self {
+        Key::String(key.to_string())
+    }
+}
+
+impl From<String> for Key {
+    fn from(key: String) -> Self {
+        Key::String(key)
+    }
+}
+
+fn unexpected(expected: &str, value: &Value) -> RuntimeError {
+    RuntimeError::new(format!("expected {expected}, got {}", value.repr()))
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Integer(a) => Ok(a),
+            _ => Err(unexpected("an integer", &value)),
+        }
+    }
+}
+
+// integers widen to floats like they do in arithmetic
+impl TryFrom<Value> for f64 {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Float(a) => Ok(a),
+            Value::Integer(a) => Ok(a as f64),
+            _ => Err(unexpected("a float", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Boolean(a) => Ok(a),
+            _ => Err(unexpected("a boolean", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(a) => Ok(a.to_string()),
+            _ => Err(unexpected("a string", &value)),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = RuntimeError>> TryFrom<Value> for Vec<T> {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Array(items) => items.iter().cloned().map(T::try_from).collect(),
+            _ => Err(unexpected("an array", &value)),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = RuntimeError>> TryFrom<Value> for BTreeMap<Key, T> {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Map(entries) => entries.iter().map(|(k, v)| Ok((k.clone(), T::try_from(v.clone())?))).collect(),
+            _ => Err(unexpected("a map", &value)),
+        }
+    }
+}
+
+// null is None, anything else has to convert
+impl<T: TryFrom<Value, Error = RuntimeError>> TryFrom<Value> for Option<T> {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::try_from(value).map(Some),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::linter::Warning;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // lint rule that reported it, errors have none
+    pub rule: Option<&'static str>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(message: String) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            rule: None,
+            line: None,
+            message,
+        }
+    }
+    pub(crate) fn error_at(message: String, line: usize) -> Self {
+        Diagnostic {
+            line: Some(line),
+            ..Self::error(message)
+        }
+    }
+    pub(crate) fn from_warning(warning: Warning) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            rule: Some(warning.rule),
+            line: Some(warning.line),
+            message: warning.message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error")?,
+            Severity::Warning => write!(f, "warning")?,
+        }
+        if let Some(rule) = self.rule {
+            write!(f, "[{rule}]")?;
+        }
+        if let Some(line) = self.line {
+            write!(f, " line {line}")?;
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
+
+// everything found while compiling a script that failed to compile
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter().filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{diagnostic}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for Diagnostics {}
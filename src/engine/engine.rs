@@ -0,0 +1,624 @@
+use crate::compiler::Compiler;
+use crate::engine::{Diagnostic, Diagnostics, RuntimeError};
+use crate::lexer::TokenStream;
+use crate::linter::{LintConfig, Linter};
+use crate::parser::{Node, NodeType, Parser};
+use crate::procedure::ProcedureRegistry;
+use crate::program::{Program, Value};
+use crate::vm::{CancelToken, Context, Limits, Progress, Scheduler, VM};
+use std::io::Write;
+use std::sync::{Arc, MutexGuard};
+
+// compiles scripts, the only way in from the embedding side
+#[derive(Default)]
+pub struct Engine {
+    procedures: Arc<ProcedureRegistry>,
+    lint: LintConfig,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Self::with_procedures(ProcedureRegistry::new())
+    }
+    pub fn with_procedures(procedures: ProcedureRegistry) -> Engine {
+        Engine {
+            procedures: Arc::new(procedures),
+            lint: LintConfig::all(),
+        }
+    }
+    // scripts compiled before keep the procedures they were compiled with
+    pub fn procedures(&mut self) -> &mut ProcedureRegistry {
+        Arc::make_mut(&mut self.procedures)
+    }
+    // every rule is enabled unless the host picks them
+    pub fn set_lint(&mut self, config: LintConfig) {
+        self.lint = config;
+    }
+    // lint warnings do not stop the compilation, they stay with the script
+    pub fn compile(&self, source: &str) -> Result<CompiledScript, Diagnostics> {
+        let stream = TokenStream::new(source.to_string()).map_err(|e| Diagnostics(vec![Diagnostic::error_at(e.message, e.line)]))?;
+        let tree = Parser::with_procedures(stream.clone(), self.procedures.clone())
+            .parse_program()
+            .map_err(|e| Diagnostics(vec![Diagnostic::error(e)]))?;
+
+        let linter = Linter::with_config(self.lint.clone());
+        let mut diagnostics: Vec<Diagnostic> = linter.check(&tree, &stream).into_iter().map(Diagnostic::from_warning).collect();
+
+        let missing = missing_flows(&tree, &stream);
+        if !missing.is_empty() {
+            diagnostics.extend(missing);
+
+            return Err(Diagnostics(diagnostics));
+        }
+
+        let mut compiler = Compiler::with_procedures(self.procedures.clone());
+
+        if let Err(e) = compiler.compile(tree).and_then(|_| compiler.link()) {
+            diagnostics.push(Diagnostic::error(e));
+
+            return Err(Diagnostics(diagnostics));
+        }
+
+        Ok(CompiledScript {
+            program: compiler.program,
+            warnings: diagnostics,
+            vm: VM::new(),
+        })
+    }
+}
+
+// a link to a flow that is not declared would fail only when the run reaches it
+fn missing_flows(tree: &Node, stream: &TokenStream) -> Vec<Diagnostic> {
+    fn visit(node: &Node, declared: &[&str], stream: &TokenStream, missing: &mut Vec<Diagnostic>) {
+        if node.node_type == NodeType::FlowLink && !declared.contains(&node.value.as_str()) {
+            missing.push(Diagnostic::error_at(format!("flow {} is not defined", node.value), stream.line_at(node.token_position)));
+        }
+        for child in &node.params {
+            visit(child, declared, stream, missing);
+        }
+    }
+
+    let declared: Vec<&str> = tree.params.iter().filter(|n| n.node_type == NodeType::FlowDeclaration).map(|n| n.value.as_str()).collect();
+    let mut missing = vec![];
+    visit(tree, &declared, stream, &mut missing);
+
+    missing
+}
+
+pub struct CompiledScript {
+    program: Program,
+    warnings: Vec<Diagnostic>,
+    vm: VM,
+}
+
+impl CompiledScript {
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+    // every run with the same seed draws the same numbers
+    pub fn set_seed(&mut self, seed: u64) {
+        self.vm.set_seed(seed);
+    }
+    // every op the run executes is written to the output, off by default
+    pub fn set_debug(&mut self, debug: bool) {
+        self.vm.set_debug(debug);
+    }
+    // print writes to stdout and eprint to stderr unless they are replaced
+    pub fn set_output(&mut self, output: Box<dyn Write + Send>) {
+        self.vm.set_output(output);
+    }
+    pub fn set_error_output(&mut self, output: Box<dyn Write + Send>) {
+        self.vm.set_error_output(output);
+    }
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.vm.set_limits(limits);
+    }
+    // spawned flows run on threads, the deterministic scheduler runs them in turns on the thread of the run
+    pub fn set_scheduler(&mut self, scheduler: Scheduler) {
+        self.vm.set_scheduler(scheduler);
+    }
+    // application state for host procedures, it is kept between runs and shared with spawned flows
+    pub fn context(&mut self) -> MutexGuard<'_, Context> {
+        self.vm.context()
+    }
+    // entry is a flow name
+    pub fn run(&mut self, entry: &str, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.vm.run(&mut self.program, &entry_name(entry), args)
+    }
+    // like run, but run_for executes it in slices
+    pub fn start(&mut self, entry: &str, args: Vec<Value>) -> Result<(), RuntimeError> {
+        self.vm.start(&mut self.program, &entry_name(entry), args)
+    }
+    // executes about ops ops of the started run, Yielded means it has to be called again
+    pub fn run_for(&mut self, ops: u64) -> Result<Progress, RuntimeError> {
+        self.vm.resume(&mut self.program, Some(ops))
+    }
+    pub fn cancel_token(&self) -> CancelToken {
+        self.vm.cancel_token()
+    }
+    // runs the entry flow as a generator, every value it yields is an item
+    pub fn iterate(&mut self, entry: &str, args: Vec<Value>) -> Result<Values<'_>, RuntimeError> {
+        self.start(entry, args)?;
+
+        Ok(Values { script: self, done: false })
+    }
+    // saves the started run, with its variables and random state, it is continued by run_for after restore
+    pub fn snapshot(&self) -> Result<Vec<u8>, RuntimeError> {
+        self.vm.snapshot(&self.program)
+    }
+    // the script has to be compiled from the same source, the context is not a part of the snapshot
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), RuntimeError> {
+        self.vm.restore(&mut self.program, snapshot)
+    }
+}
+
+// the values of a generator flow, an error ends the iteration
+pub struct Values<'a> {
+    script: &'a mut CompiledScript,
+    done: bool,
+}
+
+impl Iterator for Values<'_> {
+    type Item = Result<Value, RuntimeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.script.vm.resume(&mut self.script.program, None) {
+            Ok(Progress::Produced(value)) => Some(Ok(value)),
+            // what the flow returns at the end is not one of its values
+            Ok(_) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// "main" and "#MAIN" are the same flow
+fn entry_name(entry: &str) -> String {
+    format!("#{}", entry.trim_start_matches('#').to_uppercase())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Severity;
+    use crate::procedure::{Arity, Procedure};
+    use crate::program::Key;
+    use crate::vm::testing::compile_script;
+    use crate::vm::{Runtime, Stack};
+    use std::collections::BTreeMap;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_run_entry_with_arguments() {
+        let mut script = compile_script("#MAIN() void
+println (\"unused\")
+#PAIR(int($A), int($B)) int
+return (($A * 10) + $B)
+#WORDS($TEXT) array
+return (split($TEXT, \" \"))");
+
+        assert_eq!(script.run("pair", vec![1.into(), 2.into()]).unwrap(), Value::Integer(12));
+        assert_eq!(script.run("#PAIR", vec![3.into(), 4.into()]).unwrap(), Value::Integer(34));
+
+        let words: Vec<String> = script.run("words", vec!["a b".into()]).unwrap().try_into().unwrap();
+        assert_eq!(words, vec!["a", "b"]);
+
+        assert_eq!(script.run("main", vec![]).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_entry_arguments_use_declared_converters() {
+        let mut script = compile_script("#MAIN(int($COUNT), float($RATE), $NAME) array
+return ([$COUNT + 1, $RATE, $NAME])");
+
+        let result = script.run("main", vec!["41".into(), "0.5".into(), "x".into()]).unwrap();
+        assert_eq!(result, Value::from(vec![Value::from(42), Value::from(0.5), Value::from("x")]));
+
+        let error = script.run("main", vec!["many".into(), "0.5".into(), "x".into()]).unwrap_err();
+        assert_eq!(error.message(), "unable to int(many)");
+    }
+
+    #[test]
+    fn test_entry_without_return_is_null() {
+        // #INNER returns to #MAIN through if, its value must not leak out of #MAIN
+        let mut script = compile_script("#MAIN() void
+if (1 > 0) (#INNER, #INNER)
+#INNER() int
+return (5)");
+
+        assert_eq!(script.run("main", vec![]).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_run_errors() {
+        let mut script = compile_script("#MAIN() void
+println (\"unused\")
+#DIVIDE(int($A), int($B)) int
+return ($A / $B)");
+
+        let error = |script: &mut CompiledScript, entry: &str, args: Vec<Value>| script.run(entry, args).unwrap_err().message().to_string();
+
+        assert_eq!(error(&mut script, "missing", vec![]), "flow #MISSING is not defined");
+        assert_eq!(error(&mut script, "divide", vec![1.into()]), "flow #DIVIDE expects 2 arguments, got 1");
+        assert_eq!(error(&mut script, "divide", vec![1.into(), 0.into()]), "division by zero");
+
+        // a flow named by a value is looked up when it is called
+        let mut script = compile_script("#MAIN() void\nvar (\"#NOPE\") $F\nvar (map([1], $F)) $Y");
+        assert_eq!(error(&mut script, "main", vec![]), "flow #NOPE is not defined");
+    }
+
+    #[test]
+    fn test_execution_limits() {
+        let run = |source: &str, limits: Limits| {
+            let mut script = compile_script(source);
+            script.set_limits(limits);
+            script.run("main", vec![]).unwrap_err()
+        };
+
+        let spin = "#MAIN() void\nwhile (1 > 0) #SPIN\n#SPIN() void\nvar (1) $X";
+        let recursion = "#MAIN() void\ncall #PING () $R\n#PING() int\ncall #PONG () $R\nreturn ($R)\n#PONG() int\ncall #PING () $R\nreturn ($R)";
+
+        let fuel = Limits { fuel: Some(1000), ..Limits::default() };
+        assert_eq!(run(spin, fuel), RuntimeError::OutOfFuel(1000));
+
+        let timeout = Limits { timeout: Some(Duration::from_millis(10)), ..Limits::default() };
+        assert_eq!(run(spin, timeout), RuntimeError::Timeout(Duration::from_millis(10)));
+
+        let depth = Limits { call_depth: Some(50), ..Limits::default() };
+        assert_eq!(run(recursion, depth), RuntimeError::CallDepthExceeded(50));
+
+        let stack = Limits { stack_size: Some(2), ..Limits::default() };
+        assert_eq!(run("#MAIN() void\nvar ([1, 2, 3]) $A", stack), RuntimeError::StackOverflow(2));
+
+        let size = Limits { max_value_len: Some(100), ..Limits::default() };
+        assert_eq!(run("#MAIN() void\nvar ([]) $A\nfill_random ($A, 1000000000000, 0, 10) $B", size.clone()), RuntimeError::ValueTooLarge(100));
+        assert_eq!(run("#MAIN() void\nvar (repeat(\"ab\", 51)) $A", size.clone()), RuntimeError::ValueTooLarge(100));
+        // values built up step by step are checked too
+        let growth = "#MAIN() void\nvar (\"\") $A\nwhile (1 > 0) #GROW\n#GROW() void\nset ($A + \"abc\") $A";
+        assert_eq!(run(growth, size.clone()), RuntimeError::ValueTooLarge(100));
+        // the limit is per value, items of nested values are not added up
+        let mut script = compile_script("#MAIN() array\nvar (repeat(\"ab\", 40)) $A\nreturn ([$A, $A, $A])");
+        script.set_limits(size);
+        assert!(script.run("main", vec![]).is_ok());
+
+        // the total counts every value the run makes
+        let total = Limits { max_total_len: Some(100), ..Limits::default() };
+        assert_eq!(run("#MAIN() void
+var (repeat(\"ab\", 40)) $A
+var (repeat(\"ab\", 40)) $B", total.clone()), RuntimeError::TotalTooLarge(100));
+        assert_eq!(run(growth, total.clone()), RuntimeError::TotalTooLarge(100));
+        // shared values are not counted again, values grown in place count their growth
+        let mut script = compile_script("#MAIN() array
+var (repeat(\"ab\", 40)) $A
+return ([$A, $A, $A])");
+        script.set_limits(total.clone());
+        assert!(script.run("main", vec![]).is_ok());
+        let pushes = |count| format!("#MAIN() void\nvar ([]) $A\nfor (0, {count}) $I #ADD\n#ADD() void\npush ($A, $I)");
+        let mut script = compile_script(&pushes(90));
+        script.set_limits(total.clone());
+        assert!(script.run("main", vec![]).is_ok());
+        assert_eq!(run(&pushes(110), total), RuntimeError::TotalTooLarge(100));
+
+        // without limits a length the allocator refuses stops the run too
+        let huge = 1_000_000_000_000_000_000;
+        assert_eq!(run(&format!("#MAIN() void\nvar (repeat(\"a\", {huge})) $A"), Limits::default()), RuntimeError::ValueTooLarge(huge));
+        assert_eq!(run(&format!("#MAIN() void\nvar ([]) $A\nfill_random ($A, {huge}, 0, 10) $B"), Limits::default()), RuntimeError::ValueTooLarge(huge));
+        assert_eq!(run(&format!("#MAIN() void\nvar (format(\"{{:{huge}}}\", 1)) $A"), Limits::default()), RuntimeError::ValueTooLarge(huge));
+
+        // callbacks run under the same limits
+        let callback = "#MAIN() void\nvar (map([1, 2], #SPIN)) $A\n#SPIN($X) int\nwhile (1 > 0) #NOTHING\nreturn ($X)\n#NOTHING() void\nvar (1) $Y";
+        assert!(matches!(run(callback, Limits { fuel: Some(500), ..Limits::default() }), RuntimeError::OutOfFuel(500)));
+    }
+
+    #[test]
+    fn test_run_for_interleaves_scripts() {
+        let source = "#MAIN($N) int\nvar (0) $TOTAL\nfor (0, $N) $I #ADD\nreturn ($TOTAL)\n#ADD() void\nset ($TOTAL + $I) $TOTAL";
+        let mut first = compile_script(source);
+        let mut second = compile_script(source);
+
+        first.start("main", vec![100.into()]).unwrap();
+        second.start("main", vec![10.into()]).unwrap();
+
+        let mut results = [None, None];
+        let mut slices = 0;
+
+        while results.iter().any(Option::is_none) {
+            for (script, result) in [&mut first, &mut second].into_iter().zip(results.iter_mut()) {
+                if result.is_some() {
+                    continue;
+                }
+                if let Progress::Finished(value) = script.run_for(50).unwrap() {
+                    *result = Some(value);
+                }
+            }
+            slices += 1;
+        }
+
+        assert_eq!(results, [Some(Value::Integer(4950)), Some(Value::Integer(45))]);
+        assert!(slices > 10);
+        assert_eq!(first.run_for(50).unwrap_err().message(), "there is no started run to resume");
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let source = "#MAIN($N) string
+var (split(\"a,b\", \",\")) $NAMES
+var (0.5) $SCALE
+var (0) $TOTAL
+for (0, $N) $I #DRAW
+return (join($NAMES, \"-\") + string(float($TOTAL) * $SCALE))
+#DRAW() void
+set ($TOTAL + (rand_int(0, 1000) * $I)) $TOTAL";
+        let mut whole = compile_script(source);
+        whole.set_seed(7);
+        let expected = whole.run("main", vec![50.into()]).unwrap();
+
+        let mut paused = compile_script(source);
+        paused.set_seed(7);
+        assert_eq!(paused.snapshot().unwrap_err().message(), "there is no started run to snapshot");
+        paused.start("main", vec![50.into()]).unwrap();
+        assert_eq!(paused.run_for(200).unwrap(), Progress::Yielded);
+        let snapshot = paused.snapshot().unwrap();
+
+        // continued by a script compiled from the same source, as another process would
+        let mut restored = compile_script(source);
+        restored.restore(&snapshot).unwrap();
+        let finished = loop {
+            if let Progress::Finished(value) = restored.run_for(200).unwrap() {
+                break value;
+            }
+        };
+        assert_eq!(finished, expected);
+
+        let mut other = compile_script("#MAIN($N) int\nreturn ($N)");
+        assert_eq!(other.restore(&snapshot).unwrap_err().message(), "snapshot was taken from a different program");
+        assert_eq!(restored.restore(&snapshot[..snapshot.len() - 3]).unwrap_err().message(), "snapshot is truncated");
+        assert_eq!(restored.restore(b"garbage").unwrap_err().message(), "not a snapshot");
+    }
+
+    #[test]
+    fn test_generators() {
+        let source = "#MAIN() array
+var ([]) $OUT
+foreach #NUMBERS (3) $N #COLLECT
+foreach #PAIRS () $N #COLLECT
+return ($OUT)
+#NUMBERS($COUNT) void
+for (0, $COUNT) $I #EMIT
+yield (100)
+#EMIT() void
+yield ($I * 10)
+#PAIRS() void
+foreach #NUMBERS (2) $N #NEXT
+#NEXT() void
+yield ($N + 1)
+#COLLECT() void
+push ($OUT, $N)";
+        let mut script = compile_script(source);
+
+        let expected: Vec<Value> = [0, 10, 20, 100, 1, 11, 101].into_iter().map(Value::from).collect();
+        assert_eq!(script.run("main", vec![]).unwrap(), Value::from(expected));
+
+        let values: Result<Vec<Value>, RuntimeError> = script.iterate("numbers", vec![2.into()]).unwrap().collect();
+        assert_eq!(values.unwrap(), vec![0.into(), 10.into(), 100.into()]);
+
+        assert_eq!(script.run("numbers", vec![2.into()]).unwrap_err().message(), "flow #NUMBERS yields values, it has to be iterated");
+
+        // a paused generator is a part of the snapshot
+        script.start("main", vec![]).unwrap();
+        assert_eq!(script.run_for(40).unwrap(), Progress::Yielded);
+        let snapshot = script.snapshot().unwrap();
+        let mut restored = compile_script(source);
+        restored.restore(&snapshot).unwrap();
+        assert!(matches!(restored.run_for(10_000).unwrap(), Progress::Finished(Value::Array(items)) if items.len() == 7));
+    }
+
+    #[test]
+    fn test_coroutine_errors() {
+        let mut script = compile_script("#MAIN() void\nvar (map([1], #TWICE)) $A\n#TWICE($X) int\nyield ($X)\nreturn ($X * 2)");
+        assert_eq!(script.run("main", vec![]).unwrap_err().message(), "a callback is not able to yield");
+
+        let mut script = compile_script("#MAIN() void\nforeach #FAIL () $X #NOTHING\n#NOTHING() void\n#FAIL() void\nyield (1)\nvar (1 / 0) $Y");
+        assert_eq!(script.run("main", vec![]).unwrap_err().message(), "division by zero");
+
+        let mut script = compile_script("#MAIN() void\nvar (resume(7)) $X");
+        assert_eq!(script.run("main", vec![]).unwrap_err().message(), "coroutine 7 is not started");
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let mut script = compile_script("#MAIN() void\nwhile (1 > 0) #SPIN\n#SPIN() void\nvar (1) $X");
+        let token = script.cancel_token();
+
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        });
+
+        assert_eq!(script.run("main", vec![]).unwrap_err(), RuntimeError::Cancelled);
+        canceller.join().unwrap();
+
+        // a paused run is cancelled when it is resumed
+        script.start("main", vec![]).unwrap();
+        assert_eq!(script.run_for(100).unwrap(), Progress::Yielded);
+
+        script.cancel_token().cancel();
+        assert_eq!(script.run_for(100).unwrap_err(), RuntimeError::Cancelled);
+        assert!(!script.cancel_token().is_cancelled());
+    }
+
+    #[test]
+    fn test_compile_diagnostics() {
+        let diagnostics = Engine::new().compile("#MAIN() void\nmatch (1) (1 => #A)\n#A() void\nprint (1)").err().unwrap();
+
+        assert_eq!(diagnostics.errors().count(), 1);
+        assert_eq!(diagnostics.to_string(), "error: match must have a default _ arm");
+
+        let source = "#MAIN() void\ncall #MISSING () $X\nwhile (1 = 1) #LOOP\nif (1 > 0) (#MAIN, #ELSE)\nvar (map([1], #MAP)) $Y";
+        let diagnostics = Engine::new().compile(source).err().unwrap();
+        let errors: Vec<String> = diagnostics.errors().map(|e| e.to_string()).collect();
+
+        assert_eq!(errors, vec![
+            "error line 2: flow #MISSING is not defined",
+            "error line 3: flow #LOOP is not defined",
+            "error line 4: flow #ELSE is not defined",
+            "error line 5: flow #MAP is not defined",
+        ]);
+
+        // untrusted source gives diagnostics, it does not stop the host
+        let error = |source: &str| Engine::new().compile(source).err().unwrap().to_string();
+        assert_eq!(error("#MAIN() void\nprint (@)"), "error line 2: unexpected character \"@\"");
+        assert_eq!(error("#MAIN() void\nprint (\"abc)"), "error line 2: string is not closed");
+        assert_eq!(error("#MAIN() void\nprint (99999999999999999999)"), "error line 2: number 99999999999999999999 is out of range");
+
+        let script = compile_script("#MAIN() void\nvar (1) $UNUSED");
+        let warning = &script.warnings()[0];
+
+        assert_eq!(warning.severity, Severity::Warning);
+        assert_eq!(warning.line, Some(2));
+
+        // a catch without a name binds nothing the linter could report
+        let script = compile_script("#MAIN() void\ntry { var (1 / 0) $X\nprintln ($X) } catch { println (\"failed\") }");
+        assert!(script.warnings().is_empty());
+
+        // the host picks the rules, the environment of the process does not matter
+        let mut engine = Engine::new();
+        engine.set_lint(LintConfig::from_spec("-unused_variable").unwrap());
+        assert!(engine.compile("#MAIN() void\nvar (1) $UNUSED").unwrap().warnings().is_empty());
+    }
+
+    struct ArgCount {}
+
+    impl Procedure for ArgCount {
+        fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+            for _ in 0..argc {
+                stack.pop();
+            }
+            stack.push(Value::Integer(argc as i64));
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_host_procedures() {
+        let mut engine = Engine::new();
+        let procedures = engine.procedures();
+
+        procedures.register("arg_count", Arity::between(1, 2), ArgCount {}).unwrap();
+        procedures.register_fn("double", |x: i64| x * 2).unwrap();
+        procedures
+            .namespace("text")
+            .register_fn("pad", |text: String, width: i64| -> Result<String, String> {
+                let width = usize::try_from(width).map_err(|_| format!("width {width} is negative"))?;
+                Ok(format!("{text:>width$}"))
+            })
+            .unwrap();
+
+        let mut script = engine.compile("#MAIN() void
+println (\"unused\")
+#RUN($X) array
+text.pad (\"a\", 3) $PADDED
+return ([double($X), arg_count(1, 2), $PADDED])").unwrap();
+
+        let expected = Value::from(vec![Value::from(42), Value::from(2), Value::from("  a")]);
+        assert_eq!(script.run("run", vec![21.into()]).unwrap(), expected);
+
+        let error = script.run("run", vec!["x".into()]).unwrap_err();
+        assert_eq!(error.message(), "DOUBLE argument 1: expected an integer, got x");
+
+        let compile_error = |source: &str| engine.compile(source).err().unwrap().to_string();
+
+        assert_eq!(compile_error("#MAIN() void\nprintln (double(1, 2))"), "error: procedure DOUBLE expects 1 arguments, got 2");
+        assert_eq!(compile_error("#MAIN() void\nprintln (arg_count())"), "error: procedure ARG_COUNT expects 1 to 2 arguments, got 0");
+        // built-ins are checked the same way
+        assert_eq!(compile_error("#MAIN() void\nprintln (len(1, 2))"), "error: procedure LEN expects 1 arguments, got 2");
+        assert_eq!(compile_error("#MAIN() void\nvar ([]) $A\npush ($A)"), "error: procedure PUSH expects at least 2 arguments, got 1");
+        assert_eq!(compile_error("#MAIN() void\nprintln (slice([1]))"), "error: procedure SLICE expects 2 to 3 arguments, got 1");
+        // join takes a task handle or an array and a separator, await only a handle
+        assert_eq!(compile_error("#MAIN() void\nprintln (join([1], \"-\", 2))"), "error: procedure JOIN expects 1 to 2 arguments, got 3");
+        assert_eq!(compile_error("#MAIN() void\nprintln (await([1], \"-\"))"), "error: procedure AWAIT expects 1 arguments, got 2");
+        assert_eq!(compile_error("#MAIN() void\nprintln (text.missing(1))"), "error: procedure TEXT.MISSING is not defined");
+    }
+
+    struct Visits(Vec<String>);
+
+    struct CurrentUser {}
+
+    impl Procedure for CurrentUser {
+        fn execute(&self, _: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+            let user = rt.context().require::<String>()?.clone();
+            stack.push(Value::string(user));
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_host_context() {
+        let mut engine = Engine::new();
+        let procedures = engine.procedures();
+
+        procedures.register("current_user", Arity::exact(0), CurrentUser {}).unwrap();
+        procedures
+            .register_context_fn("visit", |visits: &mut Visits, page: String| {
+                visits.0.push(page);
+                visits.0.len() as i64
+            })
+            .unwrap();
+
+        let mut script = engine.compile("#MAIN() void
+println (\"unused\")
+#RUN() int
+visit (current_user()) $FIRST
+return (visit(\"home\") + $FIRST)").unwrap();
+
+        assert_eq!(script.run("run", vec![]).unwrap_err().message(), "context alloc::string::String is not provided");
+
+        script.context().insert(String::from("ann"));
+        script.context().insert(Visits(vec![]));
+
+        assert_eq!(script.run("run", vec![]).unwrap(), Value::Integer(3));
+        // the context stays with the script between runs
+        assert_eq!(script.run("run", vec![]).unwrap(), Value::Integer(7));
+
+        let visits = script.context().remove::<Visits>().unwrap();
+        assert_eq!(visits.0, vec!["ann", "home", "ann", "home"]);
+    }
+
+    #[test]
+    fn test_procedure_names() {
+        let mut procedures = ProcedureRegistry::new();
+
+        assert_eq!(procedures.register_fn("print", |x: Value| x).unwrap_err(), "procedure PRINT is already registered");
+        assert_eq!(procedures.register_fn("http..get", |x: Value| x).unwrap_err(), "HTTP..GET is not a valid procedure name");
+        assert_eq!(procedures.register_fn("$X", |x: Value| x).unwrap_err(), "$X is not a valid procedure name");
+
+        procedures.namespace("http").register_fn("get", |x: Value| x).unwrap();
+        assert!(procedures.contains("Http.Get"));
+    }
+
+    #[test]
+    fn test_value_conversions() {
+        let map: BTreeMap<&str, Option<i64>> = BTreeMap::from([("a", Some(1)), ("b", None)]);
+        let value = Value::from(map);
+
+        let back: BTreeMap<Key, Option<i64>> = value.clone().try_into().unwrap();
+        assert_eq!(back[&Key::from("a")], Some(1));
+        assert_eq!(back[&Key::from("b")], None);
+
+        assert_eq!(f64::try_from(Value::from(2)).unwrap(), 2.0);
+        assert!(bool::try_from(Value::from(true)).unwrap());
+        assert_eq!(i64::try_from(Value::from("x")).unwrap_err().message(), "expected an integer, got x");
+        assert!(Vec::<i64>::try_from(value).is_err());
+    }
+}
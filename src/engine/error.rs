@@ -0,0 +1,55 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+// why a script run stopped, limits of the VM have their own variants
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeError {
+    // an error of the script itself or of a value conversion
+    Failed(String),
+    // more ops executed than the fuel allows
+    OutOfFuel(u64),
+    // more values on the value stack than allowed
+    StackOverflow(usize),
+    // flows nested deeper than allowed
+    CallDepthExceeded(usize),
+    // an array, map or string longer than allowed
+    ValueTooLarge(usize),
+    // more items and bytes made by the run than allowed
+    TotalTooLarge(u64),
+    // more flows spawned by the run than allowed
+    TooManyTasks(usize),
+    Timeout(Duration),
+    // the host cancelled the run
+    Cancelled,
+}
+
+impl RuntimeError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        RuntimeError::Failed(message.into())
+    }
+    pub fn message(&self) -> String {
+        self.to_string()
+    }
+    pub fn is_limit(&self) -> bool {
+        !matches!(self, RuntimeError::Failed(_))
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::Failed(message) => write!(f, "{message}"),
+            RuntimeError::OutOfFuel(fuel) => write!(f, "script ran out of fuel after {fuel} ops"),
+            RuntimeError::StackOverflow(size) => write!(f, "value stack exceeded {size} values"),
+            RuntimeError::CallDepthExceeded(depth) => write!(f, "flow calls nested deeper than {depth}"),
+            RuntimeError::ValueTooLarge(size) => write!(f, "value exceeded {size} items"),
+            RuntimeError::TotalTooLarge(size) => write!(f, "values made by the script exceeded {size} items"),
+            RuntimeError::TooManyTasks(count) => write!(f, "script spawned more than {count} flows"),
+            RuntimeError::Timeout(timeout) => write!(f, "script exceeded the timeout of {}ms", timeout.as_millis()),
+            RuntimeError::Cancelled => write!(f, "script was cancelled"),
+        }
+    }
+}
+
+impl Error for RuntimeError {}
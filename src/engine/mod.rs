@@ -0,0 +1,8 @@
+mod convert;
+mod diagnostic;
+mod engine;
+mod error;
+
+pub use crate::engine::diagnostic::{Diagnostic, Diagnostics, Severity};
+pub use crate::engine::engine::{CompiledScript, Engine, Values};
+pub use crate::engine::error::RuntimeError;
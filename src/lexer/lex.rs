@@ -7,20 +7,45 @@ pub enum TokenName {
     Comma,
     Bracket,
     String,
+    Comment,
+}
+
+// the source is not made of tokens, the line is counted from 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    pub(crate) message: String,
+    pub(crate) line: usize,
 }
 
 #[derive(Clone)]
 pub struct TokenStream {
     tokens: Vec<Token>,
+    comments: Vec<Token>,
+    lines: Vec<usize>,
 }
 
 impl TokenStream {
-    pub fn new(input: String) -> TokenStream {
+    pub(crate) fn new(input: String) -> Result<TokenStream, LexError> {
         let chars: Vec<char> = input.chars().collect();
         let mut buffer = String::new();
         let mut last_char_idx: usize = 0;
+        // where the token in the buffer starts
+        let mut at: usize = 0;
         let mut specs: Specs = Specs::new();
         let mut tokens: Vec<Token> = Vec::new();
+        let mut comments: Vec<Token> = Vec::new();
+        let mut lines: Vec<usize> = vec![0];
+
+        for (idx, char) in chars.iter().enumerate() {
+            if *char == '\n' {
+                lines.push(idx + 1);
+            }
+        }
+
+        let error = |message: String, at: usize| LexError {
+            message,
+            line: lines.partition_point(|start| *start <= at),
+        };
 
         loop {
             let char = *chars.get(last_char_idx).unwrap_or(&'\0');
@@ -29,15 +54,33 @@ impl TokenStream {
                 break;
             }
 
+            if buffer.is_empty() {
+                at = last_char_idx;
+            }
+
+            // only a string takes the end of the source
+            if last_char_idx >= chars.len() && buffer.starts_with('"') && !(buffer.len() > 1 && buffer.ends_with('"')) {
+                return Err(error(String::from("string is not closed"), at));
+            }
+
             if let Some(spec) = specs.decide(char, buffer.clone()) {
                 specs.reset();
 
-                let token = Token::new(spec.token_name, buffer.clone(), last_char_idx);
+                let Some(spec) = spec else {
+                    return Err(error(format!("unexpected character \"{char}\""), last_char_idx));
+                };
+                if spec.token_name == TokenName::Number {
+                    check_number(&buffer).map_err(|message| error(message, at))?;
+                }
+
+                let token = Token::new(spec.token_name, buffer.clone(), at);
 
                 buffer.clear();
 
-                if token.name != TokenName::Whitespace {
-                    tokens.push(token);
+                match token.name {
+                    TokenName::Whitespace => {}
+                    TokenName::Comment => comments.push(token),
+                    _ => tokens.push(token),
                 }
 
                 continue;
@@ -47,25 +90,31 @@ impl TokenStream {
             buffer.push(char);
         }
 
-        TokenStream { tokens }
+        Ok(TokenStream { tokens, comments, lines })
     }
-    pub fn get(&mut self, i: usize) -> Option<Token> {
-        let candidate = self.tokens.get(i);
-
-        if candidate.is_some() {
-            return Some(candidate.unwrap().clone());
-        }
-
-        None
+    pub(crate) fn get(&mut self, i: usize) -> Option<Token> {
+        self.tokens.get(i).cloned()
+    }
+    pub(crate) fn comments(&self) -> &Vec<Token> {
+        &self.comments
+    }
+    pub(crate) fn line_at(&self, at: usize) -> usize {
+        self.lines.partition_point(|start| *start <= at)
     }
-    pub fn search_idx_of_closed_bracer(&mut self, mut current_position: usize) -> Option<usize> {
+    pub(crate) fn search_idx_of_closed_bracer(&mut self, mut current_position: usize) -> Option<usize> {
         let mut counts = 0;
 
+        let (open, close) = match self.tokens.get(current_position)?.value.as_str() {
+            "[" => ("[", "]"),
+            "{" => ("{", "}"),
+            _ => ("(", ")"),
+        };
+
         while let Some(token) = self.tokens.get(current_position) {
-            match token.value.as_str() {
-                "(" => counts += 1,
-                ")" => counts -= 1,
-                _ => {}
+            if token.value == open {
+                counts += 1;
+            } else if token.value == close {
+                counts -= 1;
             }
 
             if counts == 0 {
@@ -81,20 +130,16 @@ impl TokenStream {
 
 #[derive(Clone)]
 pub struct Token {
-    pub name: TokenName,
-    pub at: usize,
-    pub value: String,
+    pub(crate) name: TokenName,
+    pub(crate) at: usize,
+    pub(crate) value: String,
 }
 
 impl Token {
-    fn new(name: TokenName, value: String, pos: usize) -> Token {
-        Token {
-            name,
-            at: std::cmp::max(pos - value.len(), 0),
-            value,
-        }
+    fn new(name: TokenName, value: String, at: usize) -> Token {
+        Token { name, at, value }
     }
-    pub fn starts_with(&self, s: &str) -> bool {
+    pub(crate) fn starts_with(&self, s: &str) -> bool {
         self.value.starts_with(s)
     }
 }
@@ -104,15 +149,21 @@ struct Specs(Vec<Spec>);
 impl Specs {
     fn new() -> Self {
         Specs(Vec::from([
-            Spec::new(TokenName::Whitespace, |c, _| c.is_whitespace() || c.is_control()),
-            // 111 1 1.1 .1
+            Spec::new(TokenName::Whitespace, |c, _| c != '\0' && (c.is_whitespace() || c.is_control())),
+            // + - * / = => :, goes before numbers so a lone - is an operator
+            Spec::new(TokenName::Operator, |c, b| (b.is_empty() && "+-*/<>^=&|:?".contains(c)) || (b == "=" && c == '>') || (b == "?" && c == '?')),
+            // 111 1 1.1 .1 -1
             Spec::new(TokenName::Number, |c, b| c.is_numeric() || (c == '.' && !b.contains('.')) || (c == '-' && b.is_empty())),
-            // aaa 1aa a1a a_1a
-            Spec::new(TokenName::Word, |c, _| { c.is_alphanumeric() || "#$_".contains(c) }),
-            // + - * / =
-            Spec::new(TokenName::Operator, |c, b| b.is_empty() && "+-*/<>^=&|".contains(c)),
-            // ( ) [ ]
-            Spec::new(TokenName::Bracket, |c, b| b.is_empty() && "[]()".contains(c)),
+            // aaa 1aa a1a a_1a http.get
+            Spec::new(TokenName::Word, |c, b| {
+                c.is_alphanumeric() || "#$_".contains(c) || (c == '.' && !b.is_empty() && !b.starts_with(['#', '$']))
+            }),
+            // // comment till the end of line
+            Spec::new(TokenName::Comment, |c, b| {
+                ((b.is_empty() || b == "/") && c == '/') || (b.starts_with("//") && c != '\n' && c != '\0')
+            }),
+            // ( ) [ ] { }
+            Spec::new(TokenName::Bracket, |c, b| b.is_empty() && "[](){}".contains(c)),
             Spec::new(TokenName::Comma, |c, b| b.is_empty() && ",".contains(c)),
             // "foo bar baz"
             Spec::new(TokenName::String, |c, b| {
@@ -120,7 +171,8 @@ impl Specs {
             }),
         ]))
     }
-    pub fn decide(&mut self, c: char, b: String) -> Option<Spec> {
+    // None while a spec still takes the char, then the spec of the token, if there is one
+    pub(crate) fn decide(&mut self, c: char, b: String) -> Option<Option<Spec>> {
         let mut candidate: Option<Spec> = None;
         let mut count = 0;
 
@@ -140,17 +192,35 @@ impl Specs {
             return None;
         }
 
-        assert!(!(candidate.is_none() || b.is_empty()), "got unexpected character \"{c}\"");
+        if b.is_empty() {
+            return Some(None);
+        }
 
-        candidate
+        Some(candidate)
     }
-    pub fn reset(&mut self) {
+    pub(crate) fn reset(&mut self) {
         for spec in &mut self.0 {
             spec.reset();
         }
     }
 }
 
+// what the number spec takes has to be an integer or a float
+fn check_number(value: &str) -> Result<(), String> {
+    let parsed = match value.contains('.') {
+        true => value.parse::<f64>().is_ok_and(f64::is_finite),
+        false => value.parse::<i64>().is_ok(),
+    };
+
+    let digits = value.trim_start_matches('-').replacen('.', "", 1);
+
+    match parsed {
+        true => Ok(()),
+        false if !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit()) => Err(format!("number {value} is out of range")),
+        false => Err(format!("{value} is not a number")),
+    }
+}
+
 #[derive(Clone)]
 struct Spec {
     token_name: TokenName,
@@ -177,3 +247,59 @@ impl Spec {
         self.accepted = true;
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comments_are_collected_apart_from_tokens() {
+        let mut stream = TokenStream::new("print (1 / 2) // lint:allow(foo)\nprint (3)".to_string()).unwrap();
+
+        assert_eq!(stream.comments().len(), 1);
+        assert_eq!(stream.comments()[0].value, "// lint:allow(foo)");
+        assert_eq!(stream.line_at(stream.comments()[0].at), 1);
+        assert_eq!(stream.get(3).unwrap().value, "/");
+
+        let second_print = stream.get(7).unwrap();
+        assert_eq!(stream.line_at(second_print.at), 2);
+    }
+
+    #[test]
+    fn test_namespaced_word() {
+        let mut stream = TokenStream::new("http.get(1.5)".to_string()).unwrap();
+
+        assert_eq!(stream.get(0).unwrap().value, "http.get");
+        assert_eq!(stream.get(2).unwrap().name, TokenName::Number);
+    }
+
+    #[test]
+    fn test_minus_operator_and_negative_number() {
+        let mut stream = TokenStream::new("(1 - -2)".to_string()).unwrap();
+
+        assert_eq!(stream.get(2).unwrap().name, TokenName::Operator);
+        assert_eq!(stream.get(3).unwrap().name, TokenName::Number);
+        assert_eq!(stream.get(3).unwrap().value, "-2");
+    }
+
+    #[test]
+    fn test_trailing_whitespace() {
+        let mut stream = TokenStream::new("print (1)\n\n".to_string()).unwrap();
+
+        assert_eq!(stream.get(3).unwrap().value, ")");
+        assert!(stream.get(4).is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| TokenStream::new(source.to_string()).err().unwrap();
+
+        assert_eq!(error("print (1)\nprint (@)"), LexError { message: String::from("unexpected character \"@\""), line: 2 });
+        assert_eq!(error("print (\"abc)\n"), LexError { message: String::from("string is not closed"), line: 1 });
+        assert_eq!(error("\n\nprint (99999999999999999999)").line, 3);
+        assert_eq!(error("print (99999999999999999999)").message, "number 99999999999999999999 is out of range");
+        assert_eq!(error("print (-.)").message, "-. is not a number");
+        assert!(TokenStream::new("\"é\" + 1".to_string()).is_ok());
+    }
+}
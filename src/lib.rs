@@ -0,0 +1,21 @@
+#![allow(clippy::module_inception)]
+
+mod compiler;
+mod engine;
+mod lexer;
+mod linter;
+mod parser;
+mod procedure;
+mod program;
+mod util;
+mod vm;
+
+pub use crate::engine::{CompiledScript, Diagnostic, Diagnostics, Engine, RuntimeError, Severity, Values};
+pub use crate::procedure::{Arity, ContextFn, IntoResult, Namespace, NativeFn, Procedure, ProcedureRegistry};
+pub use crate::linter::LintConfig;
+pub use crate::program::{Key, Value};
+pub use crate::vm::{CancelToken, Context, Limits, Progress, Runtime, Scheduler, Stack};
+// parse and compile hooks of Procedure take them, they are opaque outside of the crate
+pub use crate::compiler::Compiler;
+pub use crate::lexer::Token;
+pub use crate::parser::{Node, Parser};
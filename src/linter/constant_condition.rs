@@ -0,0 +1,76 @@
+use crate::linter::rule::visit;
+use crate::linter::{Rule, Warning};
+use crate::parser::{Node, NodeType};
+use crate::program::Value;
+
+pub struct ConstantCondition {}
+
+impl Rule for ConstantCondition {
+    fn name(&self) -> &'static str {
+        "constant_condition"
+    }
+    fn check(&self, tree: &Node) -> Vec<Warning> {
+        let mut warnings = vec![];
+
+        visit(tree, &mut |node| {
+            if node.node_type != NodeType::Operation || node.value != "IF" {
+                return;
+            }
+
+            let Some(condition) = node.params.first() else {
+                return;
+            };
+
+            if !is_constant(condition) {
+                return;
+            }
+
+            let message = match fold(condition).map(|v| v.to_bool()) {
+                Some(Value::Boolean(true)) => "condition of IF is always true".to_string(),
+                Some(Value::Boolean(false)) => "condition of IF is always false".to_string(),
+                _ => "condition of IF is constant".to_string(),
+            };
+
+            warnings.push(Warning::new(self.name(), message, condition.token_position));
+        });
+
+        warnings
+    }
+}
+
+fn is_constant(node: &Node) -> bool {
+    match node.node_type {
+        NodeType::Integer | NodeType::Float | NodeType::String | NodeType::Null => true,
+        NodeType::Operation => node.is_mathematical_operation() && node.params.iter().all(is_constant),
+        _ => false,
+    }
+}
+
+fn fold(node: &Node) -> Option<Value> {
+    match node.node_type {
+        NodeType::Integer => node.value.parse::<i64>().ok().map(Value::Integer),
+        NodeType::Float => node.value.parse::<f64>().ok().map(Value::Float),
+        NodeType::String => Some(Value::string(node.value.clone())),
+        NodeType::Null => Some(Value::Null),
+        NodeType::Operation => {
+            let [l, r] = node.params.as_slice() else {
+                return None;
+            };
+            let (l, r) = (fold(l)?, fold(r)?);
+
+            // integer operations are checked, an overflow or a division by zero is not folded
+            match node.value.as_str() {
+                "+" => l.add(&r).ok(),
+                "-" => l.subtract(&r).ok(),
+                "*" => l.multiply(&r).ok(),
+                "/" => l.divide(&r).ok(),
+                ">" => l.more(&r).ok(),
+                "<" => l.less(&r).ok(),
+                "=" => l.eq(&r).ok(),
+                "??" => l.coalesce(&r).ok(),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
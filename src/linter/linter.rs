@@ -0,0 +1,211 @@
+use crate::lexer::TokenStream;
+use crate::linter::{get_rules, Rule};
+use crate::parser::Node;
+use std::collections::BTreeSet;
+
+const SUPPRESSION_PREFIX: &str = "lint:allow(";
+
+pub struct Warning {
+    pub rule: &'static str,
+    pub message: String,
+    pub position: usize,
+    pub line: usize,
+}
+
+impl Warning {
+    pub fn new(rule: &'static str, message: String, position: usize) -> Self {
+        Self {
+            rule,
+            message,
+            position,
+            line: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintConfig {
+    enabled: BTreeSet<String>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl LintConfig {
+    pub fn all() -> Self {
+        Self {
+            enabled: get_rules().iter().map(|r| r.name().to_string()).collect(),
+        }
+    }
+    pub fn none() -> Self {
+        Self {
+            enabled: BTreeSet::new(),
+        }
+    }
+    // "unused_variable,same_if_branches" enables only listed rules,
+    // "-constant_condition" keeps everything else enabled
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let names: Vec<&str> = spec.split(',').map(str::trim).filter(|n| !n.is_empty()).collect();
+
+        let mut config = if names.iter().any(|n| !n.starts_with('-')) {
+            Self::none()
+        } else {
+            Self::all()
+        };
+
+        for name in names {
+            match name.strip_prefix('-') {
+                Some(name) => config.disable(name)?,
+                None => config.enable(name)?,
+            }
+        }
+
+        Ok(config)
+    }
+    pub fn enable(&mut self, name: &str) -> Result<(), String> {
+        Self::assert_known(name)?;
+        self.enabled.insert(name.to_string());
+
+        Ok(())
+    }
+    pub fn disable(&mut self, name: &str) -> Result<(), String> {
+        Self::assert_known(name)?;
+        self.enabled.remove(name);
+
+        Ok(())
+    }
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.contains(name)
+    }
+    fn assert_known(name: &str) -> Result<(), String> {
+        if get_rules().iter().any(|r| r.name() == name) {
+            return Ok(());
+        }
+
+        Err(format!("unknown lint rule {name}"))
+    }
+}
+
+pub struct Linter {
+    config: LintConfig,
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Linter {
+    pub fn with_config(config: LintConfig) -> Linter {
+        Linter {
+            config,
+            rules: get_rules(),
+        }
+    }
+    pub fn check(&self, tree: &Node, stream: &TokenStream) -> Vec<Warning> {
+        let suppressions = Self::collect_suppressions(stream);
+
+        let mut warnings: Vec<Warning> = self
+            .rules
+            .iter()
+            .filter(|rule| self.config.is_enabled(rule.name()))
+            .flat_map(|rule| rule.check(tree))
+            .map(|mut warning| {
+                warning.line = stream.line_at(warning.position);
+                warning
+            })
+            .filter(|warning| {
+                // suppression comment works for its own line and the line below
+                !suppressions.iter().any(|(line, rule)| {
+                    (*line == warning.line || *line + 1 == warning.line) && (rule == warning.rule || rule == "all")
+                })
+            })
+            .collect();
+
+        warnings.sort_by_key(|w| w.position);
+
+        warnings
+    }
+    fn collect_suppressions(stream: &TokenStream) -> Vec<(usize, String)> {
+        let mut suppressions = Vec::new();
+
+        for comment in stream.comments() {
+            let text = comment.value.trim_start_matches('/').trim();
+
+            let Some(rules) = text.strip_prefix(SUPPRESSION_PREFIX).and_then(|r| r.strip_suffix(')')) else {
+                continue;
+            };
+
+            for rule in rules.split(',') {
+                suppressions.push((stream.line_at(comment.at), rule.trim().to_string()));
+            }
+        }
+
+        suppressions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::procedure::ProcedureRegistry;
+    use std::sync::Arc;
+
+    fn lint(source: &str, config: LintConfig) -> Vec<Warning> {
+        let stream = TokenStream::new(source.to_string()).unwrap();
+        let tree = Parser::with_procedures(stream.clone(), Arc::new(ProcedureRegistry::new())).parse_program().unwrap();
+
+        Linter::with_config(config).check(&tree, &stream)
+    }
+
+    #[test]
+    fn test_reports_each_rule() {
+        let source = "#MAIN() void
+var (1) $UNUSED
+if (1 > 2) (#A, #A)
+return (1)
+#A() void
+print (\"a\")
+#DEAD() void
+print (\"dead\")";
+
+        let warnings = lint(source, LintConfig::all());
+        let rules: Vec<&str> = warnings.iter().map(|w| w.rule).collect();
+
+        assert_eq!(rules, vec!["unused_variable", "same_if_branches", "constant_condition", "void_return_value", "unreachable_flow"]);
+        assert_eq!(warnings[0].line, 2);
+        assert_eq!(warnings[2].message, "condition of IF is always false");
+    }
+
+    #[test]
+    fn test_constant_condition_does_not_fold_errors() {
+        let source = "#MAIN() void
+if (9223372036854775807 + 1 > 0) (#A, #B)
+if ((1 / 0) > 0) (#A, #B)
+if ((6 / 3) = 2) (#A, #B)
+#A() void
+print (\"a\")
+#B() void
+print (\"b\")";
+
+        let messages: Vec<String> = lint(source, LintConfig::from_spec("constant_condition").unwrap()).into_iter().map(|w| w.message).collect();
+
+        assert_eq!(messages, vec!["condition of IF is constant", "condition of IF is constant", "condition of IF is always true"]);
+    }
+
+    #[test]
+    fn test_rules_can_be_disabled_and_suppressed() {
+        let source = "#MAIN() void
+// lint:allow(unused_variable)
+var (1) $UNUSED
+var (2) $ALSO_UNUSED
+if (1 > 2) (#MAIN, #MAIN)";
+
+        let config = LintConfig::from_spec("-constant_condition,-same_if_branches").unwrap();
+        let warnings = lint(source, config);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "variable $ALSO_UNUSED is never read");
+        assert!(LintConfig::from_spec("no_such_rule").is_err());
+    }
+}
@@ -0,0 +1,20 @@
+mod constant_condition;
+mod linter;
+mod rule;
+mod same_if_branches;
+mod unreachable_flow;
+mod unused_variable;
+mod void_return_value;
+
+pub use crate::linter::linter::{LintConfig, Linter, Warning};
+pub use crate::linter::rule::Rule;
+
+pub fn get_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(unused_variable::UnusedVariable {}),
+        Box::new(unreachable_flow::UnreachableFlow {}),
+        Box::new(same_if_branches::SameIfBranches {}),
+        Box::new(void_return_value::VoidReturnValue {}),
+        Box::new(constant_condition::ConstantCondition {}),
+    ]
+}
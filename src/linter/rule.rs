@@ -0,0 +1,23 @@
+use crate::linter::Warning;
+use crate::parser::{Node, NodeType};
+
+pub trait Rule {
+    fn name(&self) -> &'static str;
+    fn check(&self, tree: &Node) -> Vec<Warning>;
+}
+
+pub fn visit<'a>(node: &'a Node, callback: &mut impl FnMut(&'a Node)) {
+    callback(node);
+
+    for child in &node.params {
+        visit(child, callback);
+    }
+}
+
+// flow declaration params are: arguments, return type constant, statements
+pub fn split_flow(flow: &Node) -> (&[Node], Option<&Node>, &[Node]) {
+    match flow.params.iter().position(|n| n.node_type == NodeType::Constant) {
+        Some(idx) => (&flow.params[..idx], flow.params.get(idx), &flow.params[idx + 1..]),
+        None => (&[], None, &flow.params),
+    }
+}
@@ -0,0 +1,32 @@
+use crate::linter::rule::visit;
+use crate::linter::{Rule, Warning};
+use crate::parser::{Node, NodeType};
+
+pub struct SameIfBranches {}
+
+impl Rule for SameIfBranches {
+    fn name(&self) -> &'static str {
+        "same_if_branches"
+    }
+    fn check(&self, tree: &Node) -> Vec<Warning> {
+        let mut warnings = vec![];
+
+        visit(tree, &mut |node| {
+            if node.node_type != NodeType::Operation || node.value != "IF" || node.params.len() != 3 {
+                return;
+            }
+
+            let (positive, negative) = (&node.params[1], &node.params[2]);
+
+            if positive.is_flow_link() && positive.value == negative.value {
+                warnings.push(Warning::new(
+                    self.name(),
+                    format!("both branches of IF lead to {}", positive.value),
+                    node.token_position,
+                ));
+            }
+        });
+
+        warnings
+    }
+}
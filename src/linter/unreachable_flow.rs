@@ -0,0 +1,44 @@
+use crate::linter::rule::{split_flow, visit};
+use crate::linter::{Rule, Warning};
+use crate::parser::{Node, NodeType};
+use std::collections::{BTreeMap, BTreeSet};
+
+const ENTRY_FLOW: &str = "#MAIN";
+
+pub struct UnreachableFlow {}
+
+impl Rule for UnreachableFlow {
+    fn name(&self) -> &'static str {
+        "unreachable_flow"
+    }
+    fn check(&self, tree: &Node) -> Vec<Warning> {
+        let flows: BTreeMap<&str, &Node> = tree.params.iter().map(|f| (f.value.as_str(), f)).collect();
+
+        if !flows.contains_key(ENTRY_FLOW) {
+            return vec![];
+        }
+
+        let mut reached: BTreeSet<&str> = BTreeSet::from([ENTRY_FLOW]);
+        let mut queue: Vec<&str> = vec![ENTRY_FLOW];
+
+        while let Some(name) = queue.pop() {
+            let Some(flow) = flows.get(name) else {
+                continue;
+            };
+
+            for statement in split_flow(flow).2 {
+                visit(statement, &mut |node| {
+                    if node.node_type == NodeType::FlowLink && reached.insert(node.value.as_str()) {
+                        queue.push(node.value.as_str());
+                    }
+                });
+            }
+        }
+
+        tree.params
+            .iter()
+            .filter(|flow| !reached.contains(flow.value.as_str()))
+            .map(|flow| Warning::new(self.name(), format!("flow {} is never reached from {ENTRY_FLOW}", flow.value), flow.token_position))
+            .collect()
+    }
+}
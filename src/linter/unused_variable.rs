@@ -0,0 +1,45 @@
+use crate::linter::rule::{split_flow, visit};
+use crate::linter::{Rule, Warning};
+use crate::parser::{Node, NodeType};
+use std::collections::BTreeSet;
+
+pub struct UnusedVariable {}
+
+impl Rule for UnusedVariable {
+    fn name(&self) -> &'static str {
+        "unused_variable"
+    }
+    fn check(&self, tree: &Node) -> Vec<Warning> {
+        let mut bindings: Vec<&Node> = vec![];
+        let mut reads: BTreeSet<String> = BTreeSet::new();
+
+        for flow in &tree.params {
+            let (args, _, body) = split_flow(flow);
+
+            // flow arguments are declared as converter($NAME) or just $NAME
+            for arg in args {
+                if arg.node_type == NodeType::Variable {
+                    bindings.push(arg);
+                }
+                bindings.extend(arg.params.iter().filter(|n| n.node_type == NodeType::Variable));
+            }
+
+            for statement in body {
+                visit(statement, &mut |node| match node.node_type {
+                    NodeType::Variable => {
+                        reads.insert(node.value.clone());
+                    }
+                    NodeType::Binding => bindings.push(node),
+                    _ => {}
+                });
+            }
+        }
+
+        // variables are visible from called flows, so any read in the program counts
+        bindings
+            .into_iter()
+            .filter(|binding| !reads.contains(&binding.value))
+            .map(|binding| Warning::new(self.name(), format!("variable {} is never read", binding.value), binding.token_position))
+            .collect()
+    }
+}
@@ -0,0 +1,44 @@
+use crate::linter::rule::{split_flow, visit};
+use crate::linter::{Rule, Warning};
+use crate::parser::{Node, NodeType};
+
+pub struct VoidReturnValue {}
+
+impl Rule for VoidReturnValue {
+    fn name(&self) -> &'static str {
+        "void_return_value"
+    }
+    fn check(&self, tree: &Node) -> Vec<Warning> {
+        let mut warnings = vec![];
+
+        for flow in &tree.params {
+            let (_, return_type, body) = split_flow(flow);
+
+            if !return_type.is_some_and(|t| t.value.eq_ignore_ascii_case("void")) {
+                continue;
+            }
+
+            for statement in body {
+                // returns may be nested into inline blocks
+                visit(statement, &mut |node| {
+                    if node.node_type != NodeType::Operation || node.value != "RETURN" {
+                        return;
+                    }
+
+                    // return (void(...)) is the way to return nothing
+                    if node.params.first().is_some_and(|expr| expr.node_type == NodeType::Operation && expr.value == "VOID") {
+                        return;
+                    }
+
+                    warnings.push(Warning::new(
+                        self.name(),
+                        format!("flow {} is declared as void but returns a value", flow.value),
+                        node.token_position,
+                    ));
+                });
+            }
+        }
+
+        warnings
+    }
+}
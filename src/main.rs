@@ -1,44 +1,108 @@
-mod compiler;
-mod parser;
-mod lexer;
-mod program;
-mod util;
-mod procedure;
-mod vm;
-
-use crate::compiler::Compiler;
-use crate::lexer::TokenStream;
-use crate::parser::Parser;
-use crate::vm::{VM};
-use std::fs;
-use std::time::Instant;
+use rust_practice::{Engine, LintConfig, Value};
+use std::{env, fs, process};
+
+const USAGE: &str = "usage: mp run <script.mp> [--seed N] [-- args...]";
 
 fn main() {
-    let input = fs::read_to_string("./.example/array.mp")
-        .expect("Should have been able to read the file");
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let stream = TokenStream::new(input);
+    // everything after -- goes to the #MAIN parameters
+    let (args, script_args) = match args.iter().position(|arg| arg == "--") {
+        Some(position) => (&args[..position], &args[position + 1..]),
+        None => (&args[..], &[][..]),
+    };
 
-    let mut parser = Parser::new_from_stream(stream);
+    let (Some("run"), Some(path)) = (args.first().map(String::as_str), script_from_args(&args[1..])) else {
+        eprintln!("{USAGE}");
+        process::exit(2);
+    };
 
-    let tree = parser.parse_program().unwrap();
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("unable to read {path}: {e}");
+            process::exit(2);
+        }
+    };
 
-    println!("{}", tree.format(0));
+    let mut engine = Engine::new();
+    engine.set_lint(lint_from_env());
 
-    let mut compiler = Compiler::new();
+    let mut script = match engine.compile(&source) {
+        Ok(script) => script,
+        Err(diagnostics) => {
+            eprintln!("{diagnostics}");
+            process::exit(1);
+        }
+    };
 
-    compiler.compile(tree).unwrap();
+    for warning in script.warnings() {
+        eprintln!("{warning}");
+    }
 
-    let prog = &mut compiler.program;
+    script.set_debug(debug_from_env());
 
-    println!("{}", prog.to_string());
+    if let Some(seed) = seed_from_args(args) {
+        script.set_seed(seed);
+    }
 
-    let vm = VM::new();
-    let now = Instant::now();
+    // arguments come as strings, converters declared by #MAIN turn them into its types
+    let script_args = script_args.iter().map(|arg| Value::from(arg.as_str())).collect();
 
-    for _ in 0..1_000_000 {
-        vm.execute(prog);
+    match script.run("main", script_args) {
+        Ok(Value::Null) => {}
+        Ok(value) => println!("{}", value.repr()),
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
     }
+}
+
+fn script_from_args(args: &[String]) -> Option<&str> {
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                args.next();
+            }
+            _ => return Some(arg),
+        }
+    }
+
+    None
+}
 
-    println!("{}ms", now.elapsed().as_millis());
-}
\ No newline at end of file
+// --seed 42 makes random procedures repeat the same numbers
+fn seed_from_args(args: &[String]) -> Option<u64> {
+    let position = args.iter().position(|arg| arg == "--seed")?;
+
+    match args.get(position + 1).map(|seed| seed.parse::<u64>()) {
+        Some(Ok(seed)) => Some(seed),
+        _ => {
+            eprintln!("--seed expects an unsigned integer");
+            process::exit(2);
+        }
+    }
+}
+
+// DEBUG=1 prints every op before it runs
+fn debug_from_env() -> bool {
+    env::var("DEBUG").is_ok_and(|debug| debug == "1" || debug == "true")
+}
+
+// LINT="unused_variable" enables only the listed rules, LINT="-constant_condition" disables one
+fn lint_from_env() -> LintConfig {
+    let Ok(spec) = env::var("LINT") else {
+        return LintConfig::all();
+    };
+
+    match LintConfig::from_spec(&spec) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("LINT: {e}");
+            process::exit(2);
+        }
+    }
+}
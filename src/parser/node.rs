@@ -7,25 +7,28 @@ pub enum NodeType {
     String,
     Float,
     Integer,
+    Null,
     Variable,
+    Binding,
     FlowLink,
     FlowDeclaration,
+    Block,
     Program,
 }
 
 #[derive(Clone)]
 pub struct Node {
-    pub node_type: NodeType,
-    pub value: String,
-    pub params: Vec<Node>,
+    pub(crate) node_type: NodeType,
+    pub(crate) value: String,
+    pub(crate) params: Vec<Node>,
     priority: usize,
-    pub token_position: usize,
+    pub(crate) token_position: usize,
 }
 
-const OPERATION_PRIORITY: [&str; 9] = ["+", "-", "*", "/", ">", "<", "=", "^", "."];
+const OPERATION_PRIORITY: [&str; 10] = ["+", "-", "*", "/", ">", "<", "=", "^", "??", "."];
 
 impl Node {
-    pub fn new_program(params: Vec<Self>) -> Self {
+    pub(crate) fn new_program(params: Vec<Self>) -> Self {
         Self {
             node_type: NodeType::Program,
             value: "ROOT".to_string(),
@@ -35,7 +38,17 @@ impl Node {
         }
     }
 
-    pub fn new_constant(value: String, token_position: usize) -> Self {
+    pub(crate) fn new_block(params: Vec<Self>, token_position: usize) -> Self {
+        Self {
+            node_type: NodeType::Block,
+            value: "BLOCK".to_string(),
+            params,
+            priority: 4,
+            token_position,
+        }
+    }
+
+    pub(crate) fn new_constant(value: String, token_position: usize) -> Self {
         Self {
             node_type: NodeType::Constant,
             value,
@@ -45,7 +58,7 @@ impl Node {
         }
     }
 
-    pub fn new_operation(operation: String, params: Vec<Self>, token_position: usize) -> Self {
+    pub(crate) fn new_operation(operation: String, params: Vec<Self>, token_position: usize) -> Self {
         let priority = OPERATION_PRIORITY.iter().position(|n| n.eq(&operation)).unwrap_or(0) + 1;
 
         let mut node = Node {
@@ -63,23 +76,26 @@ impl Node {
         node
     }
 
-    pub fn new_number(value: String, token_position: usize) -> Self {
-        let parsed_value: String = if value.contains('.') {
-            value.parse::<f64>().unwrap().to_string()
+    pub(crate) fn new_number(value: String, token_position: usize) -> Result<Self, String> {
+        let parsed_value = if value.contains('.') {
+            value.parse::<f64>().map(|n| n.to_string()).ok()
         } else {
-            value.parse::<i64>().unwrap().to_string()
+            value.parse::<i64>().map(|n| n.to_string()).ok()
+        };
+        let Some(parsed_value) = parsed_value else {
+            return Err(format!("number {value} is out of range"));
         };
 
-        Self {
+        Ok(Self {
             node_type: if value.contains('.') { NodeType::Float } else { NodeType::Integer },
             value: parsed_value,
             params: vec![],
             priority: 4,
             token_position,
-        }
+        })
     }
 
-    pub fn new_string(value: String, token_position: usize) -> Self {
+    pub(crate) fn new_string(value: String, token_position: usize) -> Self {
         Self {
             node_type: NodeType::String,
             value: value.strip_prefix('"').unwrap().strip_suffix('"').unwrap().to_string(),
@@ -89,7 +105,17 @@ impl Node {
         }
     }
 
-    pub fn new_flow_declaration(value: String, params: Vec<Self>, token_position: usize) -> Self {
+    pub(crate) fn new_null(token_position: usize) -> Self {
+        Self {
+            node_type: NodeType::Null,
+            value: "NULL".to_string(),
+            params: vec![],
+            priority: 4,
+            token_position,
+        }
+    }
+
+    pub(crate) fn new_flow_declaration(value: String, params: Vec<Self>, token_position: usize) -> Self {
         Self {
             node_type: NodeType::FlowDeclaration,
             value: value.to_uppercase(),
@@ -99,7 +125,7 @@ impl Node {
         }
     }
 
-    pub fn new_flow_link(value: String, token_position: usize) -> Self {
+    pub(crate) fn new_flow_link(value: String, token_position: usize) -> Self {
         Self {
             node_type: NodeType::FlowLink,
             value: value.to_uppercase(),
@@ -109,7 +135,7 @@ impl Node {
         }
     }
 
-    pub fn new_variable(value: String, token_position: usize) -> Self {
+    pub(crate) fn new_variable(value: String, token_position: usize) -> Self {
         Self {
             node_type: NodeType::Variable,
             value: value.to_uppercase(),
@@ -118,34 +144,30 @@ impl Node {
             token_position,
         }
     }
-    pub fn format(&self, indent: i32) -> String {
-        let string_indent = " ".repeat((indent * 4) as usize);
-
-        let mut branches = String::new();
-
-        for n in &self.params {
-            let substr: String = n.clone().format(indent + 1);
-            branches += format!("{string_indent}└── {substr}").as_str();
+    pub(crate) fn new_binding(value: String, token_position: usize) -> Self {
+        Self {
+            node_type: NodeType::Binding,
+            value: value.to_uppercase(),
+            params: vec![],
+            priority: 4,
+            token_position,
         }
-
-        format!("{}\n{}", self.value, branches)
     }
-
-    pub fn clone_with_priority(self, priority: usize) -> Self {
+    pub(crate) fn clone_with_priority(self, priority: usize) -> Self {
         let mut self_clone = self.clone();
         self_clone.priority = priority;
         self_clone
     }
 
-    pub fn get_priority(&self) -> usize {
+    pub(crate) fn get_priority(&self) -> usize {
         self.priority
     }
 
-    pub fn deprioritize(&mut self) {
+    pub(crate) fn deprioritize(&mut self) {
         self.priority = 0;
     }
 
-    pub fn is_mathematical_operation(&self) -> bool {
+    pub(crate) fn is_mathematical_operation(&self) -> bool {
         if self.node_type != NodeType::Operation {
             return false;
         }
@@ -153,7 +175,7 @@ impl Node {
         OPERATION_PRIORITY.contains(&&*self.value) && self.value.ne(".")
     }
 
-    pub fn is_call_operation(&self) -> bool {
+    pub(crate) fn is_call_operation(&self) -> bool {
         if self.node_type != NodeType::Operation {
             return false;
         }
@@ -161,7 +183,11 @@ impl Node {
         self.value.eq(".")
     }
 
-    pub fn is_flow_link(&self) -> bool {
+    pub(crate) fn is_flow_link(&self) -> bool {
         self.node_type == NodeType::FlowLink
     }
+
+    pub(crate) fn is_block(&self) -> bool {
+        self.node_type == NodeType::Block
+    }
 }
\ No newline at end of file
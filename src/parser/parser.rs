@@ -1,27 +1,30 @@
-use crate::lexer::{TokenName, TokenStream};
+use crate::lexer::{Token, TokenName, TokenStream};
 use crate::parser::node::Node;
-use crate::procedure::get_procedures;
+use crate::procedure::ProcedureRegistry;
+use std::sync::Arc;
 
 pub struct Parser {
     last_position: usize,
     current_position: usize,
     stream: TokenStream,
+    procedures: Arc<ProcedureRegistry>,
 }
 
 impl Parser {
-    pub fn new(stream: TokenStream, current_position: usize, last_position: usize) -> Self {
+    pub(crate) fn new(stream: TokenStream, current_position: usize, last_position: usize, procedures: Arc<ProcedureRegistry>) -> Self {
         Parser {
             last_position,
             current_position,
             stream,
+            procedures,
         }
     }
 
-    pub fn new_from_stream(stream: TokenStream) -> Self {
-        Self::new(stream, 0, usize::MAX)
+    pub(crate) fn with_procedures(stream: TokenStream, procedures: Arc<ProcedureRegistry>) -> Self {
+        Self::new(stream, 0, usize::MAX, procedures)
     }
 
-    pub fn parse_program(&mut self) -> Result<Node, String> {
+    pub(crate) fn parse_program(&mut self) -> Result<Node, String> {
         let mut list = Vec::<Node>::new();
 
         loop {
@@ -40,7 +43,7 @@ impl Parser {
         Ok(Node::new_program(list))
     }
 
-    pub fn subparse_flow_declaration(&mut self) -> Result<Node, String> {
+    pub(crate) fn subparse_flow_declaration(&mut self) -> Result<Node, String> {
         let token = match self.stream.get(self.current_position) {
             None => return Err(format!("unable to find token at {:?}", self.current_position)),
             Some(token) => token
@@ -87,7 +90,7 @@ impl Parser {
         Ok(Node::new_flow_declaration(token.value, list, token.at))
     }
 
-    pub fn subparse_flow_link(&mut self) -> Result<Node, String> {
+    pub(crate) fn subparse_flow_link(&mut self) -> Result<Node, String> {
         self.current_position += 1;
         let token = match self.stream.get(self.current_position) {
             None => return Err(format!("unable to find token at {:?}", self.current_position)),
@@ -101,7 +104,7 @@ impl Parser {
         Ok(Node::new_flow_link(token.value, token.at))
     }
 
-    pub fn subparse_variable_name(&mut self) -> Result<Node, String> {
+    pub(crate) fn subparse_variable_name(&mut self) -> Result<Node, String> {
         self.current_position += 1;
         let token = match self.stream.get(self.current_position) {
             None => return Err(format!("unable to find token at {:?}", self.current_position)),
@@ -112,10 +115,57 @@ impl Parser {
             return Err(self.error(self.current_position, "variable must start with $"));
         }
 
-        Ok(Node::new_variable(token.value, token.at))
+        Ok(Node::new_binding(token.value, token.at))
     }
 
-    pub fn subparse_one_in_bracers(&mut self) -> Result<Node, String> {
+    pub(crate) fn subparse_block(&mut self) -> Result<Node, String> {
+        self.current_position += 1;
+        let open_bracer = match self.stream.get(self.current_position) {
+            None => return Err(format!("unable to find token at {:?}", self.current_position)),
+            Some(token) => token
+        };
+
+        if open_bracer.value != "{" {
+            return Err(self.error(open_bracer.at, "block must start with {"));
+        }
+
+        let mut list = Vec::<Node>::new();
+
+        loop {
+            let next_token = match self.peek() {
+                None => return Err(self.error(open_bracer.at, "missing closed block bracer")),
+                Some(token) => token
+            };
+
+            if next_token.value == "}" {
+                self.current_position += 1;
+                break;
+            }
+
+            list.push(self.subparse_node()?);
+        }
+
+        Ok(Node::new_block(list, open_bracer.at))
+    }
+
+    pub(crate) fn peek(&mut self) -> Option<Token> {
+        self.stream.get(self.current_position + 1)
+    }
+
+    pub(crate) fn skip_token(&mut self) {
+        self.current_position += 1;
+    }
+
+    pub(crate) fn next_token(&mut self) -> Result<Token, String> {
+        self.current_position += 1;
+
+        match self.stream.get(self.current_position) {
+            None => Err(format!("unable to find token at {:?}", self.current_position)),
+            Some(token) => Ok(token)
+        }
+    }
+
+    pub(crate) fn subparse_one_in_bracers(&mut self) -> Result<Node, String> {
         let sub_nodes = self.subparse_list_in_bracers(Some(1))?;
 
         if sub_nodes.len() != 1 {
@@ -125,7 +175,7 @@ impl Parser {
         Ok(sub_nodes.first().unwrap().clone())
     }
 
-    pub fn subparse_node(&mut self) -> Result<Node, String> {
+    pub(crate) fn subparse_node(&mut self) -> Result<Node, String> {
         self.current_position += 1;
         let token = match self.stream.get(self.current_position) {
             None => return Err(format!("unable to find token at {:?}", self.current_position)),
@@ -137,12 +187,12 @@ impl Parser {
         }
 
         let proc_name = token.value.to_uppercase();
-        let proc = get_procedures(&proc_name);
+        let proc = self.procedures.get(&proc_name)?.procedure.clone();
 
         proc.parse(token.clone(), self)
     }
 
-    pub fn subparse_list_in_bracers(&mut self, length: Option<usize>) -> Result<Vec<Node>, String> {
+    pub(crate) fn subparse_list_in_bracers(&mut self, length: Option<usize>) -> Result<Vec<Node>, String> {
         let start_token = self.stream.get(self.current_position).unwrap();
 
         self.current_position += 1;
@@ -164,13 +214,13 @@ impl Parser {
         let mut sub_nodes: Vec<Node> = Vec::new();
 
         if self.current_position != end_bracer_position - 1 {
-            let mut sub_parser = Parser::new(self.stream.clone(), self.current_position + 1, end_bracer_position - 1);
+            let mut sub_parser = Parser::new(self.stream.clone(), self.current_position + 1, end_bracer_position - 1, self.procedures.clone());
 
             sub_nodes = sub_parser.subparse_expressions()?;
         }
 
-        if length.is_some() && sub_nodes.len() != length.unwrap() {
-            return Err(self.error(start_token.at, format!("expected {} nodes, got {}", sub_nodes.len(), length.unwrap()).as_str()));
+        if let Some(length) = length && sub_nodes.len() != length {
+            return Err(self.error(start_token.at, format!("expected {length} nodes, got {}", sub_nodes.len()).as_str()));
         }
 
         self.current_position = end_bracer_position;
@@ -178,7 +228,7 @@ impl Parser {
         Ok(sub_nodes)
     }
 
-    pub fn subparse_expressions(&mut self) -> Result<Vec<Node>, String> {
+    pub(crate) fn subparse_expressions(&mut self) -> Result<Vec<Node>, String> {
         let mut list = Vec::<Node>::new();
         let current_token = self.stream.get(self.current_position - 1).unwrap();
 
@@ -190,7 +240,7 @@ impl Parser {
 
             match token.name {
                 TokenName::Comma => {
-                    let mut sub_parser = Self::new(self.stream.clone(), self.current_position + 1, self.last_position);
+                    let mut sub_parser = Self::new(self.stream.clone(), self.current_position + 1, self.last_position, self.procedures.clone());
                     let sub_nodes = sub_parser.subparse_expressions()?;
                     list.extend(sub_nodes);
                     break;
@@ -199,16 +249,34 @@ impl Parser {
                     if token.starts_with("#") {
                         list.push(Node::new_flow_link(token.value, token.at));
                     } else if token.starts_with("$") {
-                        list.push(Node::new_variable(token.value, token.at));
+                        let variable = Node::new_variable(token.value, token.at);
+                        list.push(self.subparse_indexes(variable)?);
+                    } else if token.value.eq_ignore_ascii_case("null") && !self.is_followed_by_bracer() {
+                        list.push(Node::new_null(token.at));
                     } else {
                         match self.subparse_list_in_bracers(None) {
                             Err(e) => return Err(e),
                             Ok(sub_nodes) => {
-                                list.push(Node::new_operation(token.value, sub_nodes, token.at));
+                                let operation = Node::new_operation(token.value, sub_nodes, token.at);
+                                list.push(self.subparse_indexes(operation)?);
                             }
                         }
                     }
                 }
+                TokenName::Bracket if token.value == "[" => {
+                    // [1, 2, "x"] array literal
+                    self.current_position -= 1;
+                    let items = self.subparse_list_in_bracers(None)?;
+                    let literal = Node::new_operation("LIST".to_string(), items, token.at);
+                    list.push(self.subparse_indexes(literal)?);
+                }
+                TokenName::Bracket if token.value == "{" => {
+                    // {"a": 1, "b": 2} map literal
+                    self.current_position -= 1;
+                    let items = self.subparse_list_in_bracers(None)?;
+                    let literal = Node::new_operation("DICT".to_string(), self.subparse_pairs(items)?, token.at);
+                    list.push(self.subparse_indexes(literal)?);
+                }
                 TokenName::Bracket => {
                     self.current_position -= 1;
                     match self.subparse_one_in_bracers() {
@@ -222,7 +290,7 @@ impl Parser {
                     list.push(Node::new_operation(token.value, vec![], token.at));
                 }
                 TokenName::Number => {
-                    list.push(Node::new_number(token.value, token.at));
+                    list.push(Node::new_number(token.value, token.at)?);
                 }
                 TokenName::String => {
                     list.push(Node::new_string(token.value, token.at));
@@ -242,7 +310,46 @@ impl Parser {
         Ok(self.prioritize(list))
     }
 
-    pub fn subparse_word(&mut self) -> Result<Node, String> {
+    fn is_followed_by_bracer(&mut self) -> bool {
+        self.current_position < self.last_position
+            && self.stream.get(self.current_position + 1).is_some_and(|t| t.value == "(")
+    }
+
+    // $A[1][-1] is AT(AT($A, 1), -1)
+    fn subparse_indexes(&mut self, mut node: Node) -> Result<Node, String> {
+        while self.current_position < self.last_position
+            && self.stream.get(self.current_position + 1).is_some_and(|t| t.value == "[")
+        {
+            let position = self.stream.get(self.current_position + 1).unwrap().at;
+            let index = self.subparse_list_in_bracers(Some(1))?;
+
+            node = Node::new_operation("AT".to_string(), vec![node, index[0].clone()], position);
+        }
+
+        Ok(node)
+    }
+
+    // key : value, key : value comes flattened from subparse_expressions
+    fn subparse_pairs(&self, items: Vec<Node>) -> Result<Vec<Node>, String> {
+        if !items.len().is_multiple_of(3) {
+            return Err(self.error(self.current_position, "map literal must consist of key: value pairs"));
+        }
+
+        let mut pairs = Vec::<Node>::new();
+
+        for chunk in items.chunks(3) {
+            if chunk[1].value != ":" {
+                return Err(self.error(chunk[1].token_position, "map key and value must be separated with :"));
+            }
+
+            pairs.push(chunk[0].clone());
+            pairs.push(chunk[2].clone());
+        }
+
+        Ok(pairs)
+    }
+
+    pub(crate) fn subparse_word(&mut self) -> Result<Node, String> {
         self.current_position += 1;
         let next_token = match self.stream.get(self.current_position) {
             None => return Err(format!("unable to find token at {:?}", self.current_position)),
@@ -355,7 +462,7 @@ mod tests {
 
     #[test]
     fn test_math_operation_replacer() {
-        let list = vec![Node::new_number("1".to_string(), 0), Node::new_operation("+".to_string(), vec![], 1), Node::new_number("2".to_string(), 2)];
+        let list = vec![Node::new_number("1".to_string(), 0).unwrap(), Node::new_operation("+".to_string(), vec![], 1), Node::new_number("2".to_string(), 2).unwrap()];
 
         let new_list = math_operations(list.clone(), 1).unwrap();
 
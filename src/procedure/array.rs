@@ -1,37 +1,27 @@
 use crate::lexer::Token;
 use crate::parser::{Node, NodeType, Parser};
+use crate::procedure::mutation::{compile_mutation, parse_mutation};
+use crate::procedure::string::char_index_of;
 use crate::procedure::Procedure;
-use crate::program::Value;
-use crate::vm::Stack;
-use rand::prelude::SmallRng;
-use rand::{Rng, RngExt, SeedableRng};
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::program::{Key, Value};
+use crate::vm::{Runtime, Stack};
+use rand::RngExt;
+use std::sync::Arc;
 use crate::compiler::Compiler;
 
-pub struct FillRandom {
-    rng: Rc<RefCell<SmallRng>>,
-}
-
-impl FillRandom {
-    pub(crate) fn new() -> FillRandom {
-        let mut rng = rand::rng();
-
-        FillRandom {
-            rng: Rc::new(RefCell::new(SmallRng::seed_from_u64(rng.next_u64()))),
-        }
-    }
-}
+pub struct FillRandom {}
 
 impl Procedure for FillRandom {
     fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
         // FILL_RANDOM ($INITIAL_VALUE, 10, -100, 100) $FILLED_VALUE
         let mut exprs = parser.subparse_list_in_bracers(Some(4))?;
 
-        assert_eq!(exprs[0].node_type, NodeType::Variable);
-        assert_eq!(exprs[1].node_type, NodeType::Integer);
-        assert_eq!(exprs[2].node_type, NodeType::Integer);
-        assert_eq!(exprs[3].node_type, NodeType::Integer);
+        if exprs[0].node_type != NodeType::Variable {
+            return Err(String::from("fill_random expects a variable as the first argument"));
+        }
+        if exprs[1..].iter().any(|expr| expr.node_type != NodeType::Integer) {
+            return Err(String::from("fill_random expects integer size, min and max"));
+        }
 
         let variable_name = parser.subparse_variable_name()?;
 
@@ -49,21 +39,32 @@ impl Procedure for FillRandom {
 
         Ok(())
     }
-    fn execute(&self, argc: usize, stack: &mut Stack) -> Result<(), String> {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
         if argc != 4 {
             return Err(String::from("argument count must be 4"));
         }
 
-        let Value::Integer(max) = stack.pop() else { todo!() };
-        let Value::Integer(min) = stack.pop() else { todo!() };
-        let Value::Integer(size) = stack.pop() else { todo!() };
-        let Value::Array(array) = stack.pop() else { todo!() };
+        let max = pop_integer(stack)?;
+        let min = pop_integer(stack)?;
+        let size = pop_integer(stack)?;
+        let mut array = pop_array(stack)?;
+
+        if min >= max {
+            return Err(format!("fill_random range {min}..{max} is empty"));
+        }
+
+        let len = array.len().saturating_add(size.max(0) as usize);
+        rt.check_len(len)?;
+
+        let items = Arc::make_mut(&mut array);
+        if items.try_reserve_exact(len - items.len()).is_err() {
+            return Err(rt.too_large(len));
+        }
 
-        let mut new_val = array.clone();
-        let addition = (0..size).map(|_| Value::Integer(self.rng.borrow_mut().random_range(min..max))).collect::<Vec<Value>>();
-        new_val.extend(addition);
+        let rng = rt.rng();
+        items.extend((0..size).map(|_| Value::Integer(rng.random_range(min..max))));
 
-        stack.push(Value::Array(new_val));
+        stack.push(Value::Array(array));
 
         Ok(())
     }
@@ -72,18 +73,371 @@ impl Procedure for FillRandom {
 pub struct At {}
 
 impl Procedure for At {
-    fn execute(&self, argc: usize, stack: &mut Stack) -> Result<(), String> {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
         if argc != 2 {
             return Err(String::from("argument count must be 2"));
         }
 
-        let Value::Integer(count) = stack.pop() else { todo!() };
-        let Value::Array(array) = stack.pop() else { todo!() };
-        let val: Value = (array)[count as usize].clone();
+        let index = stack.pop();
 
+        let val: Value = match (stack.pop(), index) {
+            (Value::Array(array), Value::Integer(index)) => array[resolve_index(index, array.len())?].clone(),
+            (Value::Array(_), _) => return Err(String::from("array index must be an integer")),
+            (Value::Map(map), key) => match map.get(&Key::from_value(&key)?) {
+                Some(value) => value.clone(),
+                None => return Err(format!("key {} is not found", key.repr())),
+            },
+            _ => return Err(String::from("only arrays and maps can be indexed")),
+        };
 
         stack.push(val);
 
         Ok(())
     }
 }
+
+pub struct List {}
+
+impl Procedure for List {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        let mut items: Vec<Value> = (0..argc).map(|_| stack.pop()).collect();
+        items.reverse();
+
+        stack.push(Value::array(items));
+
+        Ok(())
+    }
+}
+
+// negative index counts from the end of array
+pub fn resolve_index(index: i64, len: usize) -> Result<usize, String> {
+    let resolved = if index < 0 { len as i64 + index } else { index };
+
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(format!("index {index} is out of range for array of length {len}"));
+    }
+
+    Ok(resolved as usize)
+}
+
+pub struct Len {}
+
+impl Procedure for Len {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let len = match stack.pop() {
+            Value::Array(array) => array.len(),
+            Value::Map(map) => map.len(),
+            Value::String(string) => string.chars().count(),
+            _ => return Err(String::from("len expects an array, a map or a string")),
+        };
+
+        stack.push(Value::Integer(len as i64));
+
+        Ok(())
+    }
+}
+
+pub struct Push {}
+
+impl Procedure for Push {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // PUSH ($ARRAY, 1, 2)
+        parse_mutation(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_mutation(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc < 2 {
+            return Err(String::from("argument count must be at least 2"));
+        }
+
+        let mut items: Vec<Value> = (1..argc).map(|_| stack.pop()).collect();
+        items.reverse();
+
+        Arc::make_mut(last_array(stack)?).extend(items);
+
+        Ok(())
+    }
+}
+
+pub struct Pop {}
+
+impl Procedure for Pop {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // POP ($ARRAY) $LAST removes the last item of $ARRAY, an empty array is an error,
+        // POP ($ARRAY) drops the item, as with mutations the target defaults to $ARRAY
+        let mut params = parser.subparse_list_in_bracers(Some(1))?;
+
+        let target = if parser.peek().is_some_and(|t| t.starts_with("$")) {
+            parser.subparse_variable_name()?
+        } else {
+            match params.first() {
+                Some(node) if node.node_type == NodeType::Variable => Node::new_binding(node.value.clone(), node.token_position),
+                _ => return Err(format!("{} expects a variable as the first argument", token.value)),
+            }
+        };
+
+        params.push(target);
+
+        Ok(Node::new_operation(token.value, params, token.at))
+    }
+    // TAKE $ARRAY
+    // EXEC POP        leaves the item under the array
+    // SET $ARRAY
+    // VAR $LAST       DROP without a target, inside of an expression the item is the value
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        let array = match node.params.first() {
+            Some(array) if array.node_type == NodeType::Variable && node.params.len() <= 2 => array.value.clone(),
+            _ => return Err(format!("{} expects a variable as the only argument", node.value)),
+        };
+
+        sc.program.new_take(array.clone());
+        sc.program.new_exec(node.value.clone(), 1);
+        sc.program.new_set(array.clone());
+
+        match node.params.get(1) {
+            Some(target) if target.value == array => sc.program.new_drop(),
+            Some(target) => sc.program.new_var(target.value.clone()),
+            None => {}
+        }
+
+        Ok(())
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let array = last_array(stack)?;
+
+        if array.is_empty() {
+            return Err(String::from("unable to pop from an empty array"));
+        }
+
+        let last = Arc::make_mut(array).pop().unwrap();
+        let array = stack.pop();
+
+        stack.push(last);
+        stack.push(array);
+
+        Ok(())
+    }
+}
+
+pub struct Insert {}
+
+impl Procedure for Insert {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // INSERT ($ARRAY, index, value)
+        parse_mutation(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_mutation(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 3 {
+            return Err(String::from("argument count must be 3"));
+        }
+
+        let value = stack.pop();
+        let index = pop_integer(stack)?;
+        let array = last_array(stack)?;
+
+        // inserting right after the last item is allowed
+        let position = if index == array.len() as i64 { array.len() } else { resolve_index(index, array.len())? };
+
+        Arc::make_mut(array).insert(position, value);
+
+        Ok(())
+    }
+}
+
+pub struct Reverse {}
+
+impl Procedure for Reverse {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // REVERSE ($ARRAY)
+        parse_mutation(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_mutation(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        Arc::make_mut(last_array(stack)?).reverse();
+
+        Ok(())
+    }
+}
+
+pub struct Slice {}
+
+impl Procedure for Slice {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // SLICE ($ARRAY, from) or SLICE ($ARRAY, from, to), to is exclusive
+        if argc != 2 && argc != 3 {
+            return Err(String::from("argument count must be 2 or 3"));
+        }
+
+        let to = if argc == 3 { Some(pop_integer(stack)?) } else { None };
+        let from = pop_integer(stack)?;
+        let array = pop_array(stack)?;
+
+        let len = array.len() as i64;
+        let bound = |index: i64| if index < 0 { len + index } else { index };
+        let (start, end) = (bound(from), to.map_or(len, bound));
+
+        if start < 0 || end > len || start > end {
+            return Err(format!("slice {from}..{} is out of range for array of length {len}", to.unwrap_or(len)));
+        }
+
+        stack.push(Value::array(array[start as usize..end as usize].to_vec()));
+
+        Ok(())
+    }
+}
+
+pub struct Contains {}
+
+impl Procedure for Contains {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let value = stack.pop();
+
+        let found = match (stack.pop(), &value) {
+            (Value::Array(array), _) => array.contains(&value),
+            (Value::String(string), Value::String(needle)) => string.contains(needle.as_str()),
+            (Value::String(_), _) => return Err(format!("expected a string, got {}", value.repr())),
+            (other, _) => return Err(format!("expected an array or a string, got {}", other.repr())),
+        };
+
+        stack.push(Value::Boolean(found));
+
+        Ok(())
+    }
+}
+
+pub struct IndexOf {}
+
+impl Procedure for IndexOf {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // INDEX_OF ($ARRAY, value) is null when there is no such value
+        // INDEX_OF ($STRING, "substring") counts chars
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let value = stack.pop();
+
+        let position = match (stack.pop(), &value) {
+            (Value::Array(array), _) => array.iter().position(|item| *item == value),
+            (Value::String(string), Value::String(needle)) => char_index_of(&string, needle),
+            (Value::String(_), _) => return Err(format!("expected a string, got {}", value.repr())),
+            (other, _) => return Err(format!("expected an array or a string, got {}", other.repr())),
+        };
+
+        let index = match position {
+            Some(index) => Value::Integer(index as i64),
+            None => Value::Null,
+        };
+
+        stack.push(index);
+
+        Ok(())
+    }
+}
+
+pub fn pop_array(stack: &mut Stack) -> Result<Arc<Vec<Value>>, String> {
+    match stack.pop() {
+        Value::Array(array) => Ok(array),
+        other => Err(format!("expected an array, got {}", other.repr())),
+    }
+}
+
+// mutations change the array where it is, a failed one leaves it on the stack as it was
+pub fn last_array(stack: &mut Stack) -> Result<&mut Arc<Vec<Value>>, String> {
+    match stack.last_mut() {
+        Some(Value::Array(array)) => Ok(array),
+        other => Err(format!("expected an array, got {}", other.map_or(String::from("nothing"), |value| value.repr()))),
+    }
+}
+
+pub fn pop_integer(stack: &mut Stack) -> Result<i64, String> {
+    match stack.pop() {
+        Value::Integer(value) => Ok(value),
+        other => Err(format!("expected an integer, got {}", other.repr())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Value;
+    use crate::vm::testing::{integer, run};
+
+    #[test]
+    fn test_array_literals_and_indexes() {
+        let memo = run("#MAIN() void
+var ([1, 2, \"x\", [3, 4]]) $A
+var ($A[0] + $A[-3]) $SUM
+var ($A[3][len($A[3]) - 1]) $NESTED
+var ([[5], 6][0][-1]) $LITERAL
+var (len([])) $EMPTY").unwrap();
+
+        assert_eq!(integer(&memo, "$SUM"), 3);
+        assert_eq!(integer(&memo, "$NESTED"), 4);
+        assert_eq!(integer(&memo, "$LITERAL"), 5);
+        assert_eq!(integer(&memo, "$EMPTY"), 0);
+
+        let err = run("#MAIN() void\nvar ([1, 2][-3]) $A").err().unwrap();
+        assert_eq!(err, "index -3 is out of range for array of length 2");
+    }
+
+    #[test]
+    fn test_array_procedures() {
+        let memo = run("#MAIN() void
+var ([1, 2, 3]) $A
+push ($A, 4, 5)
+pop ($A)
+insert ($A, 0, 0)
+insert ($A, len($A), 9)
+set ($A, -1, 8)
+remove ($A, 1)
+var (slice($A, 1, -1)) $MIDDLE
+var (reverse($MIDDLE)) $REVERSED
+reverse ($MIDDLE)
+var (contains($A, 8)) $HAS_EIGHT
+var (index_of($A, 3)) $THREE
+var (index_of($A, 7)) $SEVEN
+pop ($A) $EIGHT
+var (pop($A) + 1) $FIVE").unwrap();
+
+        assert_eq!(memo.get("$A").unwrap().repr(), "[0,2,3]");
+        assert_eq!(integer(&memo, "$EIGHT"), 8);
+        assert_eq!(integer(&memo, "$FIVE"), 5);
+        assert_eq!(memo.get("$MIDDLE").unwrap().repr(), "[4,3,2]");
+        assert_eq!(memo.get("$REVERSED").unwrap().repr(), "[4,3,2]");
+        assert_eq!(memo.get("$HAS_EIGHT"), Some(&Value::Boolean(true)));
+        assert_eq!(integer(&memo, "$THREE"), 2);
+        assert_eq!(memo.get("$SEVEN"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn test_array_procedure_errors() {
+        assert_eq!(run("#MAIN() void\nvar ([]) $A\npop ($A)").err().unwrap(), "unable to pop from an empty array");
+        assert_eq!(run("#MAIN() void\nvar ([]) $A\npop ($A) $LAST").err().unwrap(), "unable to pop from an empty array");
+        assert_eq!(run("#MAIN() void\nvar ([1]) $A\ninsert ($A, 3, 0)").err().unwrap(), "index 3 is out of range for array of length 1");
+        assert_eq!(run("#MAIN() void\nvar (1) $A\npush ($A, 1)").err().unwrap(), "expected an array, got 1");
+        assert_eq!(run("#MAIN() void\nvar (slice([1], 0, 2)) $A").err().unwrap(), "slice 0..2 is out of range for array of length 1");
+    }
+}
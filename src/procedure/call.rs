@@ -25,6 +25,7 @@ impl Procedure for Call {
         }
 
         sc.program.new_jmp(node.params.first().unwrap().value.clone());
+        sc.program.new_result();
         sc.program.new_var(node.params.get(1).unwrap().value.clone());
 
         Ok(())
@@ -0,0 +1,153 @@
+use crate::compiler::Compiler;
+use crate::lexer::Token;
+use crate::parser::{Node, Parser};
+use crate::procedure::array::pop_integer;
+use crate::procedure::higher_order::pop_flow;
+use crate::procedure::Procedure;
+use crate::program::Value;
+use crate::vm::{Runtime, Stack};
+
+pub struct Yield {}
+
+impl Procedure for Yield {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // YIELD (expression)
+        let expr = parser.subparse_one_in_bracers()?;
+
+        Ok(Node::new_operation(token.value, vec![expr], token.at))
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        sc.sub_compile(node.params[0].clone())?;
+        sc.program.new_yield();
+
+        Ok(())
+    }
+}
+
+pub struct Coroutine {}
+
+impl Procedure for Coroutine {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // COROUTINE (#NUMBERS, 1, 10), the flow does not run until it is resumed
+        if argc == 0 {
+            return Err(String::from("argument count must be at least 1"));
+        }
+
+        let mut args: Vec<Value> = (1..argc).map(|_| stack.pop()).collect();
+        args.reverse();
+        let flow = pop_flow(stack)?;
+
+        let handle = rt.start_coroutine(&flow, args)?;
+
+        stack.push(Value::Integer(handle as i64));
+
+        Ok(())
+    }
+}
+
+pub struct Resume {}
+
+impl Procedure for Resume {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // RESUME ($COROUTINE), false once the flow has finished
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let handle = pop_handle(stack)?;
+
+        stack.push(Value::Boolean(rt.resume_coroutine(handle)?));
+
+        Ok(())
+    }
+}
+
+pub struct Current {}
+
+impl Procedure for Current {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // CURRENT ($COROUTINE), the value of the last yield
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let handle = pop_handle(stack)?;
+
+        stack.push(rt.coroutine_value(handle)?);
+
+        Ok(())
+    }
+}
+
+pub struct Close {}
+
+impl Procedure for Close {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // CLOSE ($COROUTINE), it is finished from now on and does not hold its variables any more
+        let params = parser.subparse_list_in_bracers(Some(1))?;
+
+        Ok(Node::new_operation(token.value, params, token.at))
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let handle = pop_handle(stack)?;
+
+        rt.close_coroutine(handle)
+    }
+}
+
+fn pop_handle(stack: &mut Stack) -> Result<usize, String> {
+    let handle = pop_integer(stack)?;
+
+    usize::try_from(handle).map_err(|_| format!("coroutine {handle} is not started"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Value;
+    use crate::vm::VM;
+    use crate::vm::testing::{compile, run};
+
+    #[test]
+    fn test_finished_coroutines_are_dropped() {
+        let mut program = compile("#MAIN() int
+var (0) $TOTAL
+for (0, 500) $I #ROUND
+return ($TOTAL)
+#ROUND() void
+foreach #NUMBERS (3) $N #ADD
+foreach #NUMBERS (3) $N #FIRST
+#ADD() void
+set ($TOTAL + $N) $TOTAL
+#FIRST() void
+break
+#NUMBERS($COUNT) void
+for (0, $COUNT) $I #EMIT
+#EMIT() void
+yield ($I)").unwrap();
+        let mut vm = VM::new();
+
+        assert_eq!(vm.run(&mut program, "#MAIN", vec![]).unwrap(), Value::Integer(1500));
+        // finished generators and the ones left by break are both gone
+        assert!(program.state().coroutines.is_empty());
+        assert_eq!(program.state().next_coroutine, 1000);
+
+        let memo = run("#MAIN() void
+var (coroutine(#EMIT)) $C
+var (resume($C)) $FIRST
+var (resume($C)) $SECOND
+close ($C)
+var (resume($C)) $CLOSED
+var (current($C)) $VALUE
+#EMIT() void
+yield (1)
+yield (2)").unwrap();
+
+        assert_eq!(memo.get("$SECOND"), Some(&Value::Boolean(true)));
+        assert_eq!(memo.get("$CLOSED"), Some(&Value::Boolean(false)));
+        assert_eq!(memo.get("$VALUE"), Some(&Value::Null));
+    }
+}
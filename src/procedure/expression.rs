@@ -1,22 +1,64 @@
 use crate::procedure::Procedure;
 use crate::program::Value;
-use crate::vm::Stack;
+use crate::vm::{Runtime, Stack};
 
 pub struct Expression {
-    pub op: fn(l: &Value, r: &Value) -> Value,
+    pub op: fn(l: &Value, r: &Value) -> Result<Value, String>,
 }
 
 impl Procedure for Expression {
-    fn execute(&self, argc: usize, stack: &mut Stack) -> Result<(), String> {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
         assert_eq!(argc, 2, "Procedure expects 2 arguments");
 
         let second_operand = stack.pop();
         let first_operand = stack.pop();
 
-        let new_value = (self.op)(&first_operand, &second_operand);
+        let new_value = (self.op)(&first_operand, &second_operand)?;
 
         stack.push(new_value);
 
         Ok(())
     }
 }
+
+pub struct Division {}
+
+impl Procedure for Division {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        assert_eq!(argc, 2, "Procedure expects 2 arguments");
+
+        let divisor = stack.pop();
+        let dividend = stack.pop();
+
+        // a catch tells it from the other failures of arithmetic
+        if let (Value::Integer(_), Value::Integer(0)) = (&dividend, &divisor) {
+            return Err(rt.fail("division_by_zero", String::from("division by zero")));
+        }
+
+        stack.push(dividend.divide(&divisor)?);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Value;
+    use crate::vm::testing::run;
+
+    #[test]
+    fn test_comparisons_take_the_left_operand_first() {
+        let memo = run("#MAIN() void
+var (2 > 1) $MORE
+var (1 > 2) $NOT_MORE
+var (1 < 2) $LESS
+var (2.5 > 0.5) $MORE_FLOAT
+var (1 > 1) $SAME").unwrap();
+
+        assert_eq!(memo.get("$MORE"), Some(&Value::Boolean(true)));
+        assert_eq!(memo.get("$NOT_MORE"), Some(&Value::Boolean(false)));
+        assert_eq!(memo.get("$LESS"), Some(&Value::Boolean(true)));
+        assert_eq!(memo.get("$MORE_FLOAT"), Some(&Value::Boolean(true)));
+        assert_eq!(memo.get("$SAME"), Some(&Value::Boolean(false)));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::compiler::Compiler;
+use crate::parser::{Node, NodeType};
+use crate::procedure::Procedure;
+use crate::program::Value;
+use crate::vm::{Runtime, Stack};
+
+pub struct Format {}
+
+impl Procedure for Format {
+    // FORMAT ("{} of {total:>5}, {0:.2}", $X) where {total} reads $TOTAL
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        // a literal format string is checked before the program runs
+        if let Some(template) = node.params.first().filter(|n| n.node_type == NodeType::String) {
+            check_arguments(&parse(&template.value)?, node.params.len() - 1)?;
+        }
+
+        sc.sub_compile(node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        if argc == 0 {
+            return Err(String::from("argument count must be at least 1"));
+        }
+
+        let mut args: Vec<Value> = (1..argc).map(|_| stack.pop()).collect();
+        args.reverse();
+
+        let Value::String(template) = stack.pop() else {
+            return Err(String::from("format expects a string as the first argument"));
+        };
+
+        let segments = parse(&template)?;
+        check_arguments(&segments, args.len())?;
+
+        let mut result = String::new();
+        let mut next = 0;
+
+        for segment in segments {
+            let (arg, spec) = match segment {
+                Segment::Text(text) => {
+                    result.push_str(&text);
+                    continue;
+                }
+                Segment::Placeholder(arg, spec) => (arg, spec),
+            };
+
+            // a wide padding is checked before it is made
+            let len = result.len().saturating_add(spec.width);
+            rt.check_len(len)?;
+            if result.try_reserve(spec.width).is_err() {
+                return Err(rt.too_large(len));
+            }
+
+            let value = match arg {
+                Arg::Next => {
+                    next += 1;
+                    &args[next - 1]
+                }
+                Arg::Index(idx) => &args[idx],
+                Arg::Name(name) => match rt.variable(&format!("${}", name.to_uppercase())) {
+                    Some(value) => value,
+                    None => return Err(format!("variable ${} of placeholder {{{name}}} is not defined", name.to_uppercase())),
+                },
+            };
+
+            result.push_str(&spec.apply(value)?);
+        }
+
+        stack.push(Value::string(result));
+
+        Ok(())
+    }
+}
+
+enum Segment {
+    Text(String),
+    Placeholder(Arg, Spec),
+}
+
+enum Arg {
+    Next,
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn parse(spec: &str) -> Result<Spec, String> {
+        let chars: Vec<char> = spec.chars().collect();
+        let mut result = Spec::default();
+        let mut i = 0;
+
+        if chars.len() > 1 && "<>^".contains(chars[1]) {
+            result.fill = Some(chars[0]);
+            result.align = Some(chars[1]);
+            i = 2;
+        } else if chars.first().is_some_and(|c| "<>^".contains(*c)) {
+            result.align = Some(chars[0]);
+            i = 1;
+        }
+
+        if chars.get(i) == Some(&'0') {
+            result.zero = true;
+            i += 1;
+        }
+
+        let width: String = chars[i..].iter().take_while(|c| c.is_ascii_digit()).collect();
+        i += width.len();
+        result.width = width.parse().unwrap_or(0);
+
+        if chars.get(i) == Some(&'.') {
+            let precision: String = chars[i + 1..].iter().take_while(|c| c.is_ascii_digit()).collect();
+
+            if precision.is_empty() {
+                return Err(format!("precision is missing in format spec {spec}"));
+            }
+
+            i += precision.len() + 1;
+            result.precision = precision.parse().ok();
+        }
+
+        if i != chars.len() {
+            return Err(format!("unknown format spec {spec}"));
+        }
+
+        Ok(result)
+    }
+    fn apply(&self, value: &Value) -> Result<String, String> {
+        let numeric = matches!(value, Value::Integer(_) | Value::Float(_));
+
+        let text = match (value, self.precision) {
+            (_, None) => value.repr(),
+            (Value::Float(x), Some(p)) => format!("{x:.p$}"),
+            (Value::Integer(x), Some(p)) => format!("{:.p$}", *x as f64),
+            (Value::String(s), Some(p)) => s.chars().take(p).collect(),
+            (_, Some(_)) => return Err(format!("precision is not applicable to {}", value.repr())),
+        };
+
+        let len = text.chars().count();
+
+        if len >= self.width {
+            return Ok(text);
+        }
+
+        let padding = self.width - len;
+
+        // zeros go between the sign and the digits
+        if self.zero && self.align.is_none() {
+            if !numeric {
+                return Err(format!("zero padding is not applicable to {}", value.repr()));
+            }
+
+            let (sign, digits) = text.split_at(if text.starts_with('-') { 1 } else { 0 });
+
+            return Ok(format!("{sign}{}{digits}", "0".repeat(padding)));
+        }
+
+        let fill = self.fill.unwrap_or(' ').to_string();
+        let align = self.align.unwrap_or(if numeric { '>' } else { '<' });
+
+        let (left, right) = match align {
+            '<' => (0, padding),
+            '>' => (padding, 0),
+            _ => (padding / 2, padding - padding / 2),
+        };
+
+        Ok(format!("{}{text}{}", fill.repeat(left), fill.repeat(right)))
+    }
+}
+
+// {} {0} {name} with an optional :spec, {{ and }} are escaped braces
+fn parse(template: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = vec![];
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => return Err(format!("unmatched }} in format string {template}")),
+            '{' => {
+                let mut placeholder = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(format!("unclosed {{ in format string {template}")),
+                    }
+                }
+
+                let (arg, spec) = placeholder.split_once(':').unwrap_or((&placeholder, ""));
+
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+                segments.push(Segment::Placeholder(parse_arg(arg)?, Spec::parse(spec)?));
+            }
+            c => text.push(c),
+        }
+    }
+
+    segments.push(Segment::Text(text));
+
+    Ok(segments)
+}
+
+fn parse_arg(arg: &str) -> Result<Arg, String> {
+    if arg.is_empty() {
+        return Ok(Arg::Next);
+    }
+
+    if let Ok(idx) = arg.parse::<usize>() {
+        return Ok(Arg::Index(idx));
+    }
+
+    let mut chars = arg.chars();
+
+    if chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_') {
+        return Ok(Arg::Name(arg.to_string()));
+    }
+
+    Err(format!("{{{arg}}} is not a valid placeholder"))
+}
+
+fn check_arguments(segments: &[Segment], argc: usize) -> Result<(), String> {
+    let mut next = 0;
+    let mut expected = 0;
+
+    for segment in segments {
+        match segment {
+            Segment::Placeholder(Arg::Next, _) => {
+                next += 1;
+                expected = expected.max(next);
+            }
+            Segment::Placeholder(Arg::Index(idx), _) => expected = expected.max(idx + 1),
+            _ => {}
+        }
+    }
+
+    if expected != argc {
+        return Err(format!("format string expects {expected} arguments, got {argc}"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::testing::{compile, run};
+
+    #[test]
+    fn test_format() {
+        let memo = run("#MAIN() void
+var (\"Ann\") $NAME
+var (format(\"{} lost {} points, {name}\", \"you\", -5)) $PLAIN
+var (format(\"{1}-{0}-{1}\", 1, 2)) $INDEXED
+var (format(\"[{:5}|{:<5}|{:*^7}|{:05}]\", 42, 42, \"mid\", -42)) $PADDED
+var (format(\"{:.2} {:8.3} {{literal}}\", 3.14159, 2)) $PRECISE").unwrap();
+
+        let string = |name: &str| memo.get(name).unwrap().repr();
+
+        assert_eq!(string("$PLAIN"), "you lost -5 points, Ann");
+        assert_eq!(string("$INDEXED"), "2-1-2");
+        assert_eq!(string("$PADDED"), "[   42|42   |**mid**|-0042]");
+        assert_eq!(string("$PRECISE"), "3.14    2.000 {literal}");
+    }
+
+    #[test]
+    fn test_format_errors() {
+        assert_eq!(compile("#MAIN() void\nprint (format(\"{} {}\", 1))").err().unwrap(), "format string expects 2 arguments, got 1");
+        assert_eq!(compile("#MAIN() void\nprint (format(\"{1}\", 1))").err().unwrap(), "format string expects 2 arguments, got 1");
+        assert_eq!(compile("#MAIN() void\nprint (format(\"{:x}\", 1))").err().unwrap(), "unknown format spec x");
+        assert_eq!(run("#MAIN() void\nvar (\"{}\") $F\nprint (format($F))").err().unwrap(), "format string expects 1 arguments, got 0");
+        assert_eq!(run("#MAIN() void\nprint (format(\"{missing}\"))").err().unwrap(), "variable $MISSING of placeholder {missing} is not defined");
+    }
+}
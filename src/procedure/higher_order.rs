@@ -0,0 +1,172 @@
+use crate::procedure::array::pop_array;
+use crate::procedure::Procedure;
+use crate::program::Value;
+use crate::vm::{Runtime, Stack};
+use std::cmp::Ordering;
+
+pub struct Map {}
+
+impl Procedure for Map {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // MAP ($ARRAY, #DOUBLE)
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let flow = pop_flow(stack)?;
+        let array = pop_array(stack)?;
+
+        let mut items = Vec::with_capacity(array.len());
+
+        for item in array.iter() {
+            items.push(rt.call(&flow, vec![item.clone()])?);
+        }
+
+        stack.push(Value::array(items));
+
+        Ok(())
+    }
+}
+
+pub struct Filter {}
+
+impl Procedure for Filter {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // FILTER ($ARRAY, #IS_POSITIVE)
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let flow = pop_flow(stack)?;
+        let array = pop_array(stack)?;
+
+        let mut items = vec![];
+
+        for item in array.iter() {
+            if let Value::Boolean(true) = rt.call(&flow, vec![item.clone()])?.to_bool() {
+                items.push(item.clone());
+            }
+        }
+
+        stack.push(Value::array(items));
+
+        Ok(())
+    }
+}
+
+pub struct Reduce {}
+
+impl Procedure for Reduce {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // REDUCE ($ARRAY, 0, #ADD), #ADD gets the accumulator and the item
+        if argc != 3 {
+            return Err(String::from("argument count must be 3"));
+        }
+
+        let flow = pop_flow(stack)?;
+        let mut accumulator = stack.pop();
+        let array = pop_array(stack)?;
+
+        for item in array.iter() {
+            accumulator = rt.call(&flow, vec![accumulator, item.clone()])?;
+        }
+
+        stack.push(accumulator);
+
+        Ok(())
+    }
+}
+
+pub struct SortBy {}
+
+impl Procedure for SortBy {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // SORT_BY ($ARRAY, #KEY), the sort is stable
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let flow = pop_flow(stack)?;
+        let array = pop_array(stack)?;
+
+        let mut pairs = Vec::with_capacity(array.len());
+
+        for item in array.iter() {
+            pairs.push((rt.call(&flow, vec![item.clone()])?, item.clone()));
+        }
+
+        let mut error = None;
+
+        pairs.sort_by(|(a, _), (b, _)| {
+            compare(a, b).unwrap_or_else(|e| {
+                error.get_or_insert(e);
+                Ordering::Equal
+            })
+        });
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        stack.push(Value::array(pairs.into_iter().map(|(_, item)| item).collect()));
+
+        Ok(())
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Result<Ordering, String> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(a.cmp(b)),
+        (Value::Float(a), Value::Float(b)) => Ok(a.total_cmp(b)),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        (Value::Boolean(a), Value::Boolean(b)) => Ok(a.cmp(b)),
+        _ => Err(format!("unable to compare {} with {}", a.repr(), b.repr())),
+    }
+}
+
+pub fn pop_flow(stack: &mut Stack) -> Result<String, String> {
+    match stack.pop() {
+        Value::String(name) if name.starts_with('#') => Ok(name.to_string()),
+        other => Err(format!("expected a #FLOW, got {}", other.repr())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::testing::{integer, run};
+
+    #[test]
+    fn test_higher_order_procedures() {
+        let memo = run("#MAIN() void
+var ([3, -1, 2, -5]) $A
+var (map($A, #DOUBLE)) $DOUBLED
+var (filter($A, #IS_POSITIVE)) $POSITIVE
+var (reduce($A, 0, #ADD)) $TOTAL
+var (sort_by($A, #DOUBLE)) $SORTED
+var (sort_by([{\"n\": 2}, {\"n\": 1}], #N)) $RECORDS
+#DOUBLE(int($X)) int
+return ($X * 2)
+#IS_POSITIVE(int($X)) bool
+return ($X > 0)
+#ADD(int($ACC), int($X)) int
+for (0, 2) $I #NOTHING
+return ($ACC + $X)
+#N($RECORD) int
+return (get($RECORD, \"n\"))
+#NOTHING() void").unwrap();
+
+        assert_eq!(memo.get("$DOUBLED").unwrap().repr(), "[6,-2,4,-10]");
+        assert_eq!(memo.get("$POSITIVE").unwrap().repr(), "[3,2]");
+        assert_eq!(integer(&memo, "$TOTAL"), -1);
+        assert_eq!(memo.get("$SORTED").unwrap().repr(), "[-5,-1,2,3]");
+        assert_eq!(memo.get("$RECORDS").unwrap().repr(), "[{n:1},{n:2}]");
+    }
+
+    #[test]
+    fn test_callback_errors() {
+        assert_eq!(run("#MAIN() void\nvar (map([1], #MISSING)) $A").err().unwrap(), "flow #MISSING is not defined");
+        assert_eq!(run("#MAIN() void\nvar (map([1], 1)) $A").err().unwrap(), "expected a #FLOW, got 1");
+        let err = run("#MAIN() void\nwhile (1 = 1) #STEP\n#STEP() void\nvar (map([1], #STOP)) $A\n#STOP($X) void\nbreak").err().unwrap();
+        assert_eq!(err, "break and continue are allowed only inside a loop body");
+    }
+}
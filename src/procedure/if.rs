@@ -2,14 +2,20 @@ use crate::compiler::Compiler;
 use crate::lexer::Token;
 use crate::parser::{Node, Parser};
 use crate::procedure::Procedure;
+use crate::program::Program;
 
 pub struct If {}
 
 impl Procedure for If {
     fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
         // IF (rand() > 1) (#MORE, #LESS)
+        // IF (rand() > 1) { ... } ELSE IF (...) { ... } ELSE { ... }
         let expr = parser.subparse_one_in_bracers()?;
 
+        if parser.peek().is_some_and(|t| t.value == "{") {
+            return self.parse_blocks(token, parser, expr);
+        }
+
         let hash_links = parser.subparse_list_in_bracers(Some(2))?;
 
         if !hash_links.iter().all(Node::is_flow_link) {
@@ -24,6 +30,10 @@ impl Procedure for If {
     fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
         let expr = node.params.first().unwrap().clone();
 
+        if node.params.get(1).unwrap().is_block() {
+            return self.compile_blocks(sc, node);
+        }
+
         sc.sub_compile(expr).unwrap();
 
         sc.program.new_cskip(2);
@@ -33,4 +43,96 @@ impl Procedure for If {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl If {
+    fn parse_blocks(&self, token: Token, parser: &mut Parser, expr: Node) -> Result<Node, String> {
+        let mut params = vec![expr, parser.subparse_block()?];
+
+        if !parser.peek().is_some_and(|t| t.value.eq_ignore_ascii_case("else")) {
+            return Ok(Node::new_operation(token.value, params, token.at));
+        }
+
+        parser.skip_token();
+
+        let else_token = match parser.peek() {
+            None => return Err("else must be followed by a block or if".to_string()),
+            Some(token) => token
+        };
+
+        // ELSE IF is an else block with a single nested IF
+        if else_token.value.eq_ignore_ascii_case("if") {
+            params.push(Node::new_block(vec![parser.subparse_node()?], else_token.at));
+        } else {
+            params.push(parser.subparse_block()?);
+        }
+
+        Ok(Node::new_operation(token.value, params, token.at))
+    }
+    // <expr>
+    // CSKIP 1
+    // SKIP -> else
+    // <positive block>
+    // SKIP -> end     only with else
+    // <negative block>
+    fn compile_blocks(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        sc.compile(node.params[0].clone())?;
+
+        let positive = compile_block(sc, &node.params[1])?;
+        let negative = match node.params.get(2) {
+            Some(block) => Some(compile_block(sc, block)?),
+            None => None,
+        };
+
+        sc.program.new_cskip(1);
+
+        match negative {
+            None => {
+                sc.program.new_skip(positive.len());
+                sc.program.merge(positive);
+            }
+            Some(negative) => {
+                sc.program.new_skip(positive.len() + 1);
+                sc.program.merge(positive);
+                sc.program.new_skip(negative.len());
+                sc.program.merge(negative);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn compile_block(sc: &Compiler, block: &Node) -> Result<Program, String> {
+    let mut compiler = sc.nested();
+
+    for statement in &block.params {
+        compiler.compile(statement.clone())?;
+    }
+
+    Ok(compiler.program)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::testing::{integer, run};
+
+    #[test]
+    fn test_if_with_inline_blocks() {
+        let memo = run("#MAIN() void
+var (0) $A
+var (5) $X
+if ($X > 10) { set (1) $A } else if ($X > 3) {
+    var (3) $INNER
+    set (2 + $INNER) $A
+} else { set (3) $A }
+if ($X > 0) { set ($A * 10) $A }
+if ($X < 0) { } else { }
+if ($X > 0) (#INC, #MAIN)
+#INC() void
+set ($A + 1) $A").unwrap();
+
+        assert_eq!(integer(&memo, "$A"), 51);
+        assert_eq!(integer(&memo, "$INNER"), 3);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::compiler::Compiler;
+use crate::lexer::Token;
+use crate::parser::{Node, Parser};
+use crate::procedure::Procedure;
+use crate::program::{Key, Program, Value};
+use crate::vm::{Runtime, Stack};
+
+pub struct While {}
+
+impl Procedure for While {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // WHILE ($I < 10) #BODY
+        let expr = parser.subparse_one_in_bracers()?;
+
+        let link = parser.subparse_flow_link()?;
+
+        Ok(Node::new_operation(token.value, vec![expr, link], token.at))
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        let mut condition = sc.nested();
+        condition.compile(node.params[0].clone())?;
+
+        compile_loop(sc, condition.program, Program::new(), node.params[1].value.clone(), Program::new());
+
+        Ok(())
+    }
+}
+
+pub struct For {}
+
+impl Procedure for For {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // FOR (0, 10) $I #BODY
+        let range = parser.subparse_list_in_bracers(Some(2))?;
+
+        let variable_name = parser.subparse_variable_name()?;
+
+        let link = parser.subparse_flow_link()?;
+
+        let mut params = vec![variable_name, link];
+        params.extend(range);
+
+        Ok(Node::new_operation(token.value, params, token.at))
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        let counter = node.params[0].value.clone();
+
+        sc.compile(node.params[2].clone())?;
+        sc.program.new_bind(counter.clone());
+
+        let mut condition = sc.nested();
+        condition.program.new_push(Value::string(counter.clone()));
+        condition.compile(node.params[3].clone())?;
+        condition.program.new_exec("<".to_string(), 2);
+
+        compile_loop(sc, condition.program, Program::new(), node.params[1].value.clone(), increment(counter));
+
+        Ok(())
+    }
+}
+
+pub struct Foreach {}
+
+impl Procedure for Foreach {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // FOREACH ($ARRAY) $ITEM #BODY
+        // FOREACH ($MAP) $KEY #BODY
+        // FOREACH #NUMBERS (1, 10) $ITEM #BODY, over the values the flow yields
+        if parser.peek().is_some_and(|next| next.starts_with("#")) {
+            let generator = parser.subparse_flow_link()?;
+            let args = parser.subparse_list_in_bracers(None)?;
+
+            let variable_name = parser.subparse_variable_name()?;
+
+            let link = parser.subparse_flow_link()?;
+
+            let mut params = vec![variable_name, link, generator];
+            params.extend(args);
+
+            return Ok(Node::new_operation(token.value, params, token.at));
+        }
+
+        let expr = parser.subparse_one_in_bracers()?;
+
+        let variable_name = parser.subparse_variable_name()?;
+
+        let link = parser.subparse_flow_link()?;
+
+        Ok(Node::new_operation(token.value, vec![variable_name, link, expr], token.at))
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        if node.params[2].is_flow_link() {
+            compile_generator_loop(sc, node);
+
+            return Ok(());
+        }
+
+        // hidden variables can't clash with user ones, $# is never produced by the parser
+        let array = format!("$#ARRAY{}", node.token_position);
+        let index = format!("$#INDEX{}", node.token_position);
+
+        sc.compile(node.params[2].clone())?;
+        sc.program.new_exec("ITER".to_string(), 1);
+        sc.program.new_bind(array.clone());
+        sc.program.new_push(Value::Integer(0));
+        sc.program.new_bind(index.clone());
+
+        let mut condition = Program::new();
+        condition.new_push(Value::string(index.clone()));
+        condition.new_push(Value::string(array.clone()));
+        condition.new_exec("LEN".to_string(), 1);
+        condition.new_exec("<".to_string(), 2);
+
+        let mut prelude = Program::new();
+        prelude.new_push(Value::string(array));
+        prelude.new_push(Value::string(index.clone()));
+        prelude.new_exec("AT".to_string(), 2);
+        prelude.new_bind(node.params[0].value.clone());
+
+        compile_loop(sc, condition, prelude, node.params[1].value.clone(), increment(index));
+
+        Ok(())
+    }
+}
+
+// the coroutine is resumed before every pass, the loop ends when its flow does,
+// a break leaves it suspended so it is closed after the loop
+fn compile_generator_loop(sc: &mut Compiler, node: Node) {
+    let coroutine = format!("$#COROUTINE{}", node.token_position);
+
+    for param in node.params.iter().skip(2) {
+        sc.sub_compile(param.clone()).unwrap();
+    }
+    sc.program.new_exec("COROUTINE".to_string(), node.params.len() - 2);
+    sc.program.new_bind(coroutine.clone());
+
+    let mut condition = Program::new();
+    condition.new_push(Value::string(coroutine.clone()));
+    condition.new_exec("RESUME".to_string(), 1);
+
+    let mut prelude = Program::new();
+    prelude.new_push(Value::string(coroutine.clone()));
+    prelude.new_exec("CURRENT".to_string(), 1);
+    prelude.new_bind(node.params[0].value.clone());
+
+    compile_loop(sc, condition, prelude, node.params[1].value.clone(), Program::new());
+
+    sc.program.new_push(Value::string(coroutine));
+    sc.program.new_exec("CLOSE".to_string(), 1);
+}
+
+pub struct Iter {}
+
+impl Procedure for Iter {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        // maps are iterated over their keys
+        let items = match stack.pop() {
+            Value::Array(array) => Value::Array(array),
+            Value::Map(map) => Value::array(map.keys().map(Key::to_value).collect()),
+            other => return Err(format!("unable to iterate over {}", other.repr())),
+        };
+
+        stack.push(items);
+
+        Ok(())
+    }
+}
+
+pub struct Break {}
+
+impl Procedure for Break {
+    fn compile(&self, sc: &mut Compiler, _: Node) -> Result<(), String> {
+        sc.program.new_break();
+
+        Ok(())
+    }
+}
+
+pub struct Continue {}
+
+impl Procedure for Continue {
+    fn compile(&self, sc: &mut Compiler, _: Node) -> Result<(), String> {
+        sc.program.new_continue();
+
+        Ok(())
+    }
+}
+
+fn increment(variable: String) -> Program {
+    let mut step = Program::new();
+
+    step.new_push(Value::string(variable.clone()));
+    step.new_push(Value::Integer(1));
+    step.new_exec("+".to_string(), 2);
+    step.new_set(variable);
+
+    step
+}
+
+// LOOP n
+// <condition>
+// CSKIP 1
+// SKIP -> ENDLOOP
+// <prelude>
+// JMP #BODY       continue returns here
+// <step>
+// BSKIP -> <condition>
+// ENDLOOP         break jumps here
+fn compile_loop(sc: &mut Compiler, condition: Program, prelude: Program, body: String, step: Program) {
+    let (c, p, s) = (condition.len(), prelude.len(), step.len());
+
+    sc.program.new_loop(c + p + s + 5);
+    sc.program.merge(condition);
+    sc.program.new_cskip(1);
+    sc.program.new_skip(p + s + 2);
+    sc.program.merge(prelude);
+    sc.program.new_jmp(body);
+    sc.program.merge(step);
+    sc.program.new_bskip(c + p + s + 4);
+    sc.program.new_endloop();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::testing::{integer, run};
+
+    #[test]
+    fn test_for_and_foreach_loops() {
+        let memo = run("#MAIN() void
+var (0) $TOTAL
+var (0) $ITEMS
+var (array(int(0))) $EMPTY
+fill_random ($EMPTY, 4, 1, 2) $ONES
+for (0, 5) $I #ADD
+for (0, 2) $I #ADD
+foreach ($ONES) $ITEM #COUNT
+#ADD() void
+set ($TOTAL + $I) $TOTAL
+#COUNT() void
+set ($ITEMS + $ITEM) $ITEMS").unwrap();
+
+        assert_eq!(integer(&memo, "$TOTAL"), 11);
+        assert_eq!(integer(&memo, "$ITEMS"), 4);
+    }
+
+    #[test]
+    fn test_while_with_break_and_continue() {
+        let memo = run("#MAIN() void
+var (0) $I
+var (0) $SEEN
+while (1 = 1) #STEP
+#STEP() void
+set ($I + 1) $I
+if ($I > 6) (#STOP, #NOTHING)
+if ($I > 3) (#SKIP, #NOTHING)
+set ($SEEN + 1) $SEEN
+#STOP() void
+break
+#SKIP() void
+continue
+#NOTHING() void").unwrap();
+
+        assert_eq!(integer(&memo, "$I"), 7);
+        assert_eq!(integer(&memo, "$SEEN"), 3);
+    }
+
+    #[test]
+    fn test_break_outside_of_loop_is_an_error() {
+        assert!(run("#MAIN() void\nbreak").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use crate::compiler::Compiler;
+use crate::lexer::Token;
+use crate::parser::{Node, Parser};
+use crate::procedure::mutation::{compile_mutation, parse_mutation};
+use crate::procedure::array::resolve_index;
+use crate::procedure::Procedure;
+use crate::program::{Key, Value};
+use crate::vm::{Runtime, Stack};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+pub struct Dict {}
+
+impl Procedure for Dict {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // DICT ("a", 1, "b", 2) is what {"a": 1, "b": 2} compiles to
+        if !argc.is_multiple_of(2) {
+            return Err(String::from("argument count must be even"));
+        }
+
+        let mut pairs: Vec<Value> = (0..argc).map(|_| stack.pop()).collect();
+        pairs.reverse();
+
+        let mut map = BTreeMap::new();
+
+        for pair in pairs.chunks(2) {
+            map.insert(Key::from_value(&pair[0])?, pair[1].clone());
+        }
+
+        stack.push(Value::map(map));
+
+        Ok(())
+    }
+}
+
+pub struct Get {}
+
+impl Procedure for Get {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // GET ($MAP, "key") or GET ($MAP, "key", default)
+        if argc != 2 && argc != 3 {
+            return Err(String::from("argument count must be 2 or 3"));
+        }
+
+        let default = if argc == 3 { Some(stack.pop()) } else { None };
+        let key = stack.pop();
+        let map = pop_map(stack)?;
+
+        let value = match (map.get(&Key::from_value(&key)?), default) {
+            (Some(value), _) => value.clone(),
+            (None, Some(default)) => default,
+            (None, None) => return Err(format!("key {} is not found", key.repr())),
+        };
+
+        stack.push(value);
+
+        Ok(())
+    }
+}
+
+pub struct Has {}
+
+impl Procedure for Has {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let key = stack.pop();
+        let map = pop_map(stack)?;
+
+        stack.push(Value::Boolean(map.contains_key(&Key::from_value(&key)?)));
+
+        Ok(())
+    }
+}
+
+pub struct Keys {}
+
+impl Procedure for Keys {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let map = pop_map(stack)?;
+
+        stack.push(Value::array(map.keys().map(Key::to_value).collect()));
+
+        Ok(())
+    }
+}
+
+pub struct Values {}
+
+impl Procedure for Values {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let map = pop_map(stack)?;
+
+        stack.push(Value::array(map.values().cloned().collect()));
+
+        Ok(())
+    }
+}
+
+pub struct Remove {}
+
+impl Procedure for Remove {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // REMOVE ($MAP, "key")
+        // REMOVE ($ARRAY, index)
+        parse_mutation(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_mutation(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let key = stack.pop();
+
+        // the container stays on the stack, a failed remove leaves it as it was
+        match (stack.last_mut(), key) {
+            (Some(Value::Map(map)), key) => {
+                let key = Key::from_value(&key)?;
+                Arc::make_mut(map).remove(&key);
+            }
+            (Some(Value::Array(array)), Value::Integer(index)) => {
+                let position = resolve_index(index, array.len())?;
+                Arc::make_mut(array).remove(position);
+            }
+            (Some(Value::Array(_)), _) => return Err(String::from("array index must be an integer")),
+            (other, _) => return Err(format!("expected a map or an array, got {}", other.map_or(String::from("nothing"), |value| value.repr()))),
+        }
+
+        Ok(())
+    }
+}
+
+fn pop_map(stack: &mut Stack) -> Result<Arc<BTreeMap<Key, Value>>, String> {
+    match stack.pop() {
+        Value::Map(map) => Ok(map),
+        other => Err(format!("expected a map, got {}", other.repr())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Value;
+    use crate::vm::testing::{integer, run};
+
+    #[test]
+    fn test_maps() {
+        let memo = run("#MAIN() void
+var ({\"b\": 2, \"a\": 1, 3: [4]}) $M
+var ($M[\"a\"] + get($M, \"missing\", 10) + $M[3][0]) $SUM
+set ($M, \"c\", 3)
+remove ($M, \"a\")
+var (set($M, \"d\", 4)) $COPY
+var (0) $TOTAL
+foreach (remove($M, 3)) $KEY #ADD
+var (has($M, \"c\")) $HAS_C
+var ({} = {}) $EMPTY_EQ
+#ADD() void
+set ($TOTAL + $M[$KEY]) $TOTAL").unwrap();
+
+        assert_eq!(integer(&memo, "$SUM"), 15);
+        assert_eq!(integer(&memo, "$TOTAL"), 5);
+        assert_eq!(memo.get("$HAS_C"), Some(&Value::Boolean(true)));
+        assert_eq!(memo.get("$EMPTY_EQ"), Some(&Value::Boolean(true)));
+        assert_eq!(memo.get("$M").unwrap().repr(), "{3:[4],b:2,c:3}");
+        assert_eq!(memo.get("$COPY").unwrap().repr(), "{3:[4],b:2,c:3,d:4}");
+
+        let err = run("#MAIN() void\nvar ({1.5: 1}) $M").err().unwrap();
+        assert_eq!(err, "unable to use 1.5 as a map key");
+    }
+}
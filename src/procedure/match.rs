@@ -0,0 +1,163 @@
+use crate::compiler::Compiler;
+use crate::lexer::{Token, TokenName};
+use crate::parser::{Node, NodeType, Parser};
+use crate::procedure::Procedure;
+use crate::program::Value;
+
+const WILDCARD: &str = "_";
+
+pub struct Match {}
+
+impl Procedure for Match {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // MATCH ($VALUE) (1 => #ONE, "two" => #TWO, true => #YES, null => #NONE, _ => #DEFAULT)
+        let expr = parser.subparse_one_in_bracers()?;
+
+        let open_bracer = parser.next_token()?;
+
+        if open_bracer.value != "(" {
+            return Err("match arms must be enclosed in bracers".to_string());
+        }
+
+        let mut params = vec![expr];
+
+        loop {
+            let mut pattern = parse_pattern(parser.next_token()?)?;
+
+            if parser.next_token()?.value != "=>" {
+                return Err("match arm must look like pattern => #FLOW".to_string());
+            }
+
+            pattern.params.push(parser.subparse_flow_link()?);
+            params.push(pattern);
+
+            let separator = parser.next_token()?;
+
+            match separator.value.as_str() {
+                "," => continue,
+                ")" => break,
+                _ => return Err(format!("unexpected {} in match arms", separator.value)),
+            }
+        }
+
+        Ok(Node::new_operation(token.value, params, token.at))
+    }
+    // <expr>
+    // CASE pattern -> arm jump    one per arm, the value is popped by the matched one
+    // CASE _       -> default jump
+    // JMP #ARM
+    // SKIP -> end
+    // ...
+    // JMP #DEFAULT
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        let arms = &node.params[1..];
+
+        let Some(default_idx) = arms.iter().position(|arm| arm.value == WILDCARD) else {
+            return Err("match must have a default _ arm".to_string());
+        };
+
+        if default_idx != arms.len() - 1 {
+            return Err("default _ arm of match must be the last one".to_string());
+        }
+
+        let mut patterns: Vec<Value> = vec![];
+
+        for arm in &arms[..default_idx] {
+            let pattern = pattern_value(arm)?;
+
+            if patterns.contains(&pattern) {
+                return Err(format!("match arm {} is duplicated", pattern.repr()));
+            }
+
+            patterns.push(pattern);
+        }
+
+        sc.compile(node.params[0].clone())?;
+
+        let k = arms.len();
+
+        for (i, pattern) in patterns.into_iter().enumerate() {
+            sc.program.new_case(Some(pattern), k + i - 1);
+        }
+        sc.program.new_case(None, 2 * k - 2);
+
+        for (i, arm) in arms.iter().enumerate() {
+            sc.program.new_jmp(arm.params[0].value.clone());
+
+            if i != k - 1 {
+                sc.program.new_skip(2 * (k - 1 - i) - 1);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_pattern(token: Token) -> Result<Node, String> {
+    match token.name {
+        TokenName::Number => Node::new_number(token.value, token.at),
+        TokenName::String => Ok(Node::new_string(token.value, token.at)),
+        TokenName::Word if token.value.eq_ignore_ascii_case("null") => Ok(Node::new_null(token.at)),
+        TokenName::Word if token.value == WILDCARD => Ok(Node::new_constant(token.value, token.at)),
+        TokenName::Word if token.value.eq_ignore_ascii_case("true") || token.value.eq_ignore_ascii_case("false") => {
+            Ok(Node::new_constant(token.value.to_uppercase(), token.at))
+        }
+        _ => Err(format!("{} is not allowed as a match pattern", token.value)),
+    }
+}
+
+fn pattern_value(arm: &Node) -> Result<Value, String> {
+    match arm.node_type {
+        NodeType::Integer => arm.value.parse::<i64>().map(Value::Integer).map_err(|_| format!("{} is out of range", arm.value)),
+        NodeType::Float => arm.value.parse::<f64>().map(Value::Float).map_err(|_| format!("{} is out of range", arm.value)),
+        NodeType::String => Ok(Value::string(arm.value.clone())),
+        NodeType::Constant => Ok(Value::Boolean(arm.value == "TRUE")),
+        NodeType::Null => Ok(Value::Null),
+        _ => Err(format!("{} is not allowed as a match pattern", arm.value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::testing::{integer, run};
+
+    #[test]
+    fn test_match_arms() {
+        let memo = run("#MAIN() void
+var (\"b\") $CODE
+var (0) $R
+match ($CODE) (1 => #ONE, \"b\" => #B, true => #ONE, 2.5 => #ONE, _ => #DEFAULT)
+switch (7) (1 => #ONE, _ => #DEFAULT)
+match (1) (_ => #ONE)
+#ONE() void
+set ($R + 1) $R
+#B() void
+set ($R + 10) $R
+#DEFAULT() void
+set ($R + 100) $R").unwrap();
+
+        assert_eq!(integer(&memo, "$R"), 111);
+        assert!(run("#MAIN() void\nmatch (1) (1 => #MAIN)").is_err());
+        assert!(run("#MAIN() void\nmatch (1) (1 => #MAIN, 1 => #MAIN, _ => #MAIN)").is_err());
+
+        let memo = run("#MAIN() void
+var (0) $R
+match (0 - 1) (1 => #POS, -1 => #NEG, _ => #MAIN)
+#POS() void
+set (1) $R
+#NEG() void
+set (-1) $R").unwrap();
+
+        assert_eq!(integer(&memo, "$R"), -1);
+    }
+
+    #[test]
+    fn test_out_of_range_pattern_is_an_error() {
+        // the lexer rejects such a literal, a node made by a procedure is not checked by it
+        let mut arm = Node::new_constant("99999999999999999999".to_string(), 0);
+        arm.node_type = NodeType::Integer;
+
+        assert_eq!(pattern_value(&arm).err().unwrap(), "99999999999999999999 is out of range");
+    }
+}
@@ -0,0 +1,281 @@
+use crate::procedure::array::pop_integer;
+use crate::procedure::Procedure;
+use crate::program::Value;
+use crate::vm::{Runtime, Stack};
+use std::cmp::Ordering;
+
+// integers stay integers where the result allows it, the rest works on floats
+pub struct FloatFunction {
+    pub name: &'static str,
+    pub op: fn(f64) -> f64,
+    pub domain: fn(f64) -> bool,
+}
+
+impl Procedure for FloatFunction {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // SQRT (2), LN (1.5), SIN ($ANGLE)
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let x = pop_float(stack)?;
+
+        if !(self.domain)(x) {
+            return Err(format!("{}({x}) is out of the domain", self.name));
+        }
+
+        let result = (self.op)(x);
+
+        if !result.is_finite() {
+            return Err(format!("{}({x}) is not a finite number", self.name));
+        }
+
+        stack.push(Value::Float(result));
+
+        Ok(())
+    }
+}
+
+pub struct Rounding {
+    pub op: fn(f64) -> f64,
+}
+
+impl Procedure for Rounding {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // FLOOR (1.5), CEIL (1.5), ROUND (1.5) are integers
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let result = match stack.pop() {
+            Value::Integer(x) => x,
+            Value::Float(x) => to_integer((self.op)(x))?,
+            other => return Err(format!("expected a number, got {}", other.repr())),
+        };
+
+        stack.push(Value::Integer(result));
+
+        Ok(())
+    }
+}
+
+pub struct Abs {}
+
+impl Procedure for Abs {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let result = match stack.pop() {
+            Value::Integer(x) => Value::Integer(x.checked_abs().ok_or(format!("abs({x}) overflows an integer"))?),
+            Value::Float(x) => Value::Float(x.abs()),
+            other => return Err(format!("expected a number, got {}", other.repr())),
+        };
+
+        stack.push(result);
+
+        Ok(())
+    }
+}
+
+pub struct Extremum {
+    pub wanted: Ordering,
+}
+
+impl Procedure for Extremum {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // MIN (1, 2, 3), MAX (1.5, 0.5)
+        if argc == 0 {
+            return Err(String::from("argument count must be at least 1"));
+        }
+
+        let mut result = stack.pop();
+
+        for _ in 1..argc {
+            let operand = stack.pop();
+
+            if compare(&operand, &result)? == self.wanted {
+                result = operand;
+            }
+        }
+
+        // a single argument is still checked to be a number
+        compare(&result, &result)?;
+
+        stack.push(result);
+
+        Ok(())
+    }
+}
+
+pub struct Clamp {}
+
+impl Procedure for Clamp {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // CLAMP ($X, min, max)
+        if argc != 3 {
+            return Err(String::from("argument count must be 3"));
+        }
+
+        let max = stack.pop();
+        let min = stack.pop();
+        let x = stack.pop();
+
+        if compare(&min, &max)? == Ordering::Greater {
+            return Err(format!("clamp range {}..{} is empty", min.repr(), max.repr()));
+        }
+
+        let result = if compare(&x, &min)? == Ordering::Less {
+            min
+        } else if compare(&x, &max)? == Ordering::Greater {
+            max
+        } else {
+            x
+        };
+
+        stack.push(result);
+
+        Ok(())
+    }
+}
+
+pub struct Pow {}
+
+impl Procedure for Pow {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // POW (2, 10) is an integer, POW (2, 0.5) is a float
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let result = match (stack.pop(), stack.pop()) {
+            (exponent @ Value::Integer(_), base @ Value::Integer(_)) => base.power(&exponent)?,
+            (exponent, base) => Value::Float(to_float(&base)?).power(&Value::Float(to_float(&exponent)?))?,
+        };
+
+        stack.push(result);
+
+        Ok(())
+    }
+}
+
+pub struct Gcd {}
+
+impl Procedure for Gcd {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // GCD (12, 18)
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let (mut a, mut b) = (pop_integer(stack)?.unsigned_abs(), pop_integer(stack)?.unsigned_abs());
+
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+
+        stack.push(Value::Integer(i64::try_from(a).map_err(|_| format!("gcd {a} overflows an integer"))?));
+
+        Ok(())
+    }
+}
+
+pub struct Constant {
+    pub value: f64,
+}
+
+impl Procedure for Constant {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // PI (), E ()
+        if argc != 0 {
+            return Err(String::from("argument count must be zero"));
+        }
+
+        stack.push(Value::Float(self.value));
+
+        Ok(())
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Result<Ordering, String> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(a.cmp(b)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b).ok_or(String::from("unable to compare NaN")),
+        _ => Err(format!("unable to compare {} with {}", a.repr(), b.repr())),
+    }
+}
+
+fn to_float(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Integer(x) => Ok(*x as f64),
+        Value::Float(x) => Ok(*x),
+        other => Err(format!("expected a number, got {}", other.repr())),
+    }
+}
+
+fn to_integer(x: f64) -> Result<i64, String> {
+    if !x.is_finite() || x < i64::MIN as f64 || x > i64::MAX as f64 {
+        return Err(format!("{x} does not fit an integer"));
+    }
+
+    Ok(x as i64)
+}
+
+fn pop_float(stack: &mut Stack) -> Result<f64, String> {
+    to_float(&stack.pop())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Value;
+    use crate::vm::testing::{integer, run};
+
+    #[test]
+    fn test_math_procedures() {
+        let memo = run("#MAIN() void
+var (2 ^ 10) $POWER
+var (pow(2, 0.5)) $ROOT
+var (abs(-3)) $ABS
+var (min(3, -1, 2)) $MIN
+var (max(1.5, 2.5)) $MAX
+var (clamp(15, 0, 10)) $CLAMPED
+var (floor(-1.5)) $FLOOR
+var (ceil(1.2)) $CEIL
+var (round(2.5)) $ROUND
+var (floor(7)) $SAME
+var (gcd(12, -18)) $GCD
+var (sqrt(16)) $SQRT
+var (ln(e())) $LN
+var (cos(pi())) $COS").unwrap();
+
+        assert_eq!(integer(&memo, "$POWER"), 1024);
+        assert_eq!(memo.get("$ROOT"), Some(&Value::Float(2f64.sqrt())));
+        assert_eq!(integer(&memo, "$ABS"), 3);
+        assert_eq!(integer(&memo, "$MIN"), -1);
+        assert_eq!(memo.get("$MAX"), Some(&Value::Float(2.5)));
+        assert_eq!(integer(&memo, "$CLAMPED"), 10);
+        assert_eq!(integer(&memo, "$FLOOR"), -2);
+        assert_eq!(integer(&memo, "$CEIL"), 2);
+        assert_eq!(integer(&memo, "$ROUND"), 3);
+        assert_eq!(integer(&memo, "$SAME"), 7);
+        assert_eq!(integer(&memo, "$GCD"), 6);
+        assert_eq!(memo.get("$SQRT"), Some(&Value::Float(4.0)));
+        assert_eq!(memo.get("$LN"), Some(&Value::Float(1.0)));
+        assert_eq!(memo.get("$COS"), Some(&Value::Float(-1.0)));
+    }
+
+    #[test]
+    fn test_math_domain_errors() {
+        assert_eq!(run("#MAIN() void\nvar (sqrt(-1)) $A").err().unwrap(), "sqrt(-1) is out of the domain");
+        assert_eq!(run("#MAIN() void\nvar (ln(0)) $A").err().unwrap(), "ln(0) is out of the domain");
+        assert_eq!(run("#MAIN() void\nvar (2 ^ 64) $A").err().unwrap(), "2 ^ 64 overflows an integer");
+        assert_eq!(run("#MAIN() void\nvar (9223372036854775807 + 1) $A").err().unwrap(), "9223372036854775807 + 1 overflows an integer");
+        assert_eq!(run("#MAIN() void\nvar (0 - 9223372036854775807 - 2) $A").err().unwrap(), "-9223372036854775807 - 2 overflows an integer");
+        assert_eq!(run("#MAIN() void\nvar (4611686018427387904 * 2) $A").err().unwrap(), "4611686018427387904 * 2 overflows an integer");
+        let min = "#MAIN() void\nvar (0 - 9223372036854775807 - 1) $A\nvar ($A / (0 - 1)) $B";
+        assert_eq!(run(min).err().unwrap(), "-9223372036854775808 / -1 overflows an integer");
+        assert_eq!(run("#MAIN() void\nvar (min(1, 2.5)) $A").err().unwrap(), "unable to compare 1 with 2.5");
+        assert_eq!(run("#MAIN() void\nvar (exp(1000)) $A").err().unwrap(), "exp(1000) is not a finite number");
+    }
+}
@@ -1,66 +1,175 @@
 mod array;
 mod call;
+mod coroutine;
 mod expression;
+mod format;
+mod higher_order;
 mod r#if;
+mod loops;
+mod map;
+mod r#match;
+mod math;
 mod print;
+mod mutation;
+mod native;
+mod parallel;
 mod procedure;
 mod rand;
+mod registry;
 mod r#return;
+mod r#try;
+mod set;
+mod string;
 mod sum;
 mod type_converter;
 mod var;
 
+pub use crate::procedure::native::{ContextFn, IntoResult, NativeFn};
 pub use crate::procedure::procedure::Procedure;
+pub use crate::procedure::registry::{Arity, Namespace, ProcedureRegistry};
 use crate::program::Value;
+use std::cmp::Ordering;
+use std::f64::consts;
 
-pub fn get_procedures(name: &str) -> Box<dyn Procedure> {
-    match name {
-        "CALL" => Box::new(call::Call {}),
-        "IF" => Box::new(r#if::If {}),
-        "PRINT" => Box::new(print::Print {}),
-        "RETURN" => Box::new(r#return::Return {}),
-        "VAR" => Box::new(var::Var {}),
-        "RAND" => Box::new(rand::Rand::new()),
-        "SUM" => Box::new(sum::Sum {}),
-        "BOOL" => Box::new(type_converter::TypeConverter { op: Value::to_bool }),
-        "FILL_RANDOM" => Box::new(array::FillRandom::new()),
-        "AT" => Box::new(array::At {}),
-        "FLOAT" => Box::new(type_converter::TypeConverter {
-            op: Value::to_float,
-        }),
-        "STRING" => Box::new(type_converter::TypeConverter {
-            op: Value::to_string,
-        }),
-        "INT" => Box::new(type_converter::TypeConverter {
-            op: Value::to_integer,
-        }),
-        "ARRAY" => Box::new(type_converter::TypeConverter {
-            op: |l: &Value| {
-                Value::Array(match l {
-                    Value::Integer(_) => Vec::<Value>::new(),
-                    Value::Float(_) => Vec::<Value>::new(),
-                    Value::Boolean(_) => Vec::<Value>::new(),
-                    Value::String(_) => Vec::<Value>::new(),
-                    Value::Array(_) => Vec::<Value>::new(),
-                    _ => {panic!("unable to create array of {}", l.repr())}
-                })
-            }
-        }),
-        "VOID" => Box::new(type_converter::TypeConverter {
-            op: |_| Value::Integer(0),
-        }),
-        "+" => Box::new(expression::Expression { op: Value::add }),
-        "-" => Box::new(expression::Expression {
-            op: Value::subtract,
-        }),
-        "/" => Box::new(expression::Expression { op: Value::divide }),
-        "*" => Box::new(expression::Expression {
-            op: Value::multiply,
-        }),
-        "^" => Box::new(expression::Expression { op: Value::power }),
-        "=" => Box::new(expression::Expression { op: Value::eq }),
-        "<" => Box::new(expression::Expression { op: Value::less }),
-        ">" => Box::new(expression::Expression { op: Value::more }),
-        _ => panic!("Unknown procedure {name}"),
-    }
+// statements like IF or CALL check their syntax when they are parsed, their params are not arguments
+pub fn register_builtins(registry: &mut ProcedureRegistry) {
+    registry.builtin("CALL", Arity::any(), call::Call {});
+    registry.builtin("IF", Arity::any(), r#if::If {});
+    registry.builtin("MATCH", Arity::any(), r#match::Match {});
+    registry.builtin("SWITCH", Arity::any(), r#match::Match {});
+    registry.builtin("WHILE", Arity::any(), loops::While {});
+    registry.builtin("FOR", Arity::any(), loops::For {});
+    registry.builtin("FOREACH", Arity::any(), loops::Foreach {});
+    registry.builtin("BREAK", Arity::any(), loops::Break {});
+    registry.builtin("CONTINUE", Arity::any(), loops::Continue {});
+    registry.builtin("PRINT", Arity::exact(1), print::Print { newline: false, error: false });
+    registry.builtin("PRINTLN", Arity::exact(1), print::Print { newline: true, error: false });
+    registry.builtin("EPRINT", Arity::exact(1), print::Print { newline: false, error: true });
+    registry.builtin("EPRINTLN", Arity::exact(1), print::Print { newline: true, error: true });
+    registry.builtin("RETURN", Arity::any(), r#return::Return {});
+    registry.builtin("TRY", Arity::any(), r#try::Try {});
+    registry.builtin("THROW", Arity::between(1, 2), r#try::Throw {});
+    registry.builtin("VAR", Arity::any(), var::Var {});
+    registry.builtin("SET", Arity::any(), set::Set {});
+    registry.builtin("RAND", Arity::exact(0), rand::Rand {});
+    registry.builtin("RAND_INT", Arity::exact(2), rand::RandInt {});
+    registry.builtin("CHOICE", Arity::exact(1), rand::Choice {});
+    registry.builtin("SHUFFLE", Arity::exact(1), rand::Shuffle {});
+    registry.builtin("SAMPLE", Arity::exact(2), rand::Sample {});
+    registry.builtin("SUM", Arity::at_least(1), sum::Sum {});
+    registry.builtin("BOOL", Arity::exact(1), type_converter::TypeConverter { op: |l: &Value| Ok(l.to_bool()) });
+    registry.builtin("FILL_RANDOM", Arity::exact(4), array::FillRandom {});
+    registry.builtin("AT", Arity::exact(2), array::At {});
+    registry.builtin("LEN", Arity::exact(1), array::Len {});
+    registry.builtin("LIST", Arity::any(), array::List {});
+    registry.builtin("PUSH", Arity::at_least(2), array::Push {});
+    registry.builtin("POP", Arity::exact(1), array::Pop {});
+    registry.builtin("INSERT", Arity::exact(3), array::Insert {});
+    registry.builtin("REVERSE", Arity::exact(1), array::Reverse {});
+    registry.builtin("SLICE", Arity::between(2, 3), array::Slice {});
+    registry.builtin("CONTAINS", Arity::exact(2), array::Contains {});
+    registry.builtin("INDEX_OF", Arity::exact(2), array::IndexOf {});
+    registry.builtin("SUBSTR", Arity::between(2, 3), string::Substr {});
+    registry.builtin("UPPER", Arity::exact(1), string::Transform { op: str::to_uppercase });
+    registry.builtin("LOWER", Arity::exact(1), string::Transform { op: str::to_lowercase });
+    registry.builtin("TRIM", Arity::exact(1), string::Transform {
+        op: |s: &str| s.trim().to_string(),
+    });
+    registry.builtin("SPLIT", Arity::exact(2), string::Split {});
+    registry.builtin("REPLACE", Arity::exact(3), string::Replace {});
+    registry.builtin("STARTS_WITH", Arity::exact(2), string::Affix {
+        op: |s: &str, prefix: &str| s.starts_with(prefix),
+    });
+    registry.builtin("ENDS_WITH", Arity::exact(2), string::Affix {
+        op: |s: &str, suffix: &str| s.ends_with(suffix),
+    });
+    registry.builtin("REPEAT", Arity::exact(2), string::Repeat {});
+    registry.builtin("CHARS", Arity::exact(1), string::Chars {});
+    registry.builtin("ABS", Arity::exact(1), math::Abs {});
+    registry.builtin("MIN", Arity::at_least(1), math::Extremum { wanted: Ordering::Less });
+    registry.builtin("MAX", Arity::at_least(1), math::Extremum { wanted: Ordering::Greater });
+    registry.builtin("CLAMP", Arity::exact(3), math::Clamp {});
+    registry.builtin("FLOOR", Arity::exact(1), math::Rounding { op: f64::floor });
+    registry.builtin("CEIL", Arity::exact(1), math::Rounding { op: f64::ceil });
+    registry.builtin("ROUND", Arity::exact(1), math::Rounding { op: f64::round });
+    registry.builtin("POW", Arity::exact(2), math::Pow {});
+    registry.builtin("GCD", Arity::exact(2), math::Gcd {});
+    registry.builtin("SQRT", Arity::exact(1), math::FloatFunction { name: "sqrt", op: f64::sqrt, domain: |x| x >= 0.0 });
+    registry.builtin("EXP", Arity::exact(1), math::FloatFunction { name: "exp", op: f64::exp, domain: |_| true });
+    registry.builtin("LN", Arity::exact(1), math::FloatFunction { name: "ln", op: f64::ln, domain: |x| x > 0.0 });
+    registry.builtin("LOG10", Arity::exact(1), math::FloatFunction { name: "log10", op: f64::log10, domain: |x| x > 0.0 });
+    registry.builtin("SIN", Arity::exact(1), math::FloatFunction { name: "sin", op: f64::sin, domain: f64::is_finite });
+    registry.builtin("COS", Arity::exact(1), math::FloatFunction { name: "cos", op: f64::cos, domain: f64::is_finite });
+    registry.builtin("TAN", Arity::exact(1), math::FloatFunction { name: "tan", op: f64::tan, domain: f64::is_finite });
+    registry.builtin("ASIN", Arity::exact(1), math::FloatFunction { name: "asin", op: f64::asin, domain: |x| (-1.0..=1.0).contains(&x) });
+    registry.builtin("ACOS", Arity::exact(1), math::FloatFunction { name: "acos", op: f64::acos, domain: |x| (-1.0..=1.0).contains(&x) });
+    registry.builtin("ATAN", Arity::exact(1), math::FloatFunction { name: "atan", op: f64::atan, domain: |_| true });
+    registry.builtin("PI", Arity::exact(0), math::Constant { value: consts::PI });
+    registry.builtin("E", Arity::exact(0), math::Constant { value: consts::E });
+    registry.builtin("FORMAT", Arity::at_least(1), format::Format {});
+    registry.builtin("MAP", Arity::exact(2), higher_order::Map {});
+    registry.builtin("FILTER", Arity::exact(2), higher_order::Filter {});
+    registry.builtin("REDUCE", Arity::exact(3), higher_order::Reduce {});
+    registry.builtin("SORT_BY", Arity::exact(2), higher_order::SortBy {});
+    registry.builtin("DICT", Arity::any(), map::Dict {});
+    registry.builtin("GET", Arity::between(2, 3), map::Get {});
+    registry.builtin("HAS", Arity::exact(2), map::Has {});
+    registry.builtin("KEYS", Arity::exact(1), map::Keys {});
+    registry.builtin("VALUES", Arity::exact(1), map::Values {});
+    registry.builtin("REMOVE", Arity::exact(2), map::Remove {});
+    registry.builtin("ITER", Arity::exact(1), loops::Iter {});
+    registry.builtin("YIELD", Arity::any(), coroutine::Yield {});
+    registry.builtin("COROUTINE", Arity::at_least(1), coroutine::Coroutine {});
+    registry.builtin("RESUME", Arity::exact(1), coroutine::Resume {});
+    registry.builtin("CURRENT", Arity::exact(1), coroutine::Current {});
+    registry.builtin("CLOSE", Arity::exact(1), coroutine::Close {});
+    registry.builtin("SPAWN", Arity::at_least(1), parallel::Spawn {});
+    registry.builtin("JOIN", Arity::between(1, 2), parallel::Join {});
+    registry.builtin("AWAIT", Arity::exact(1), parallel::Join {});
+    registry.builtin("CHAN", Arity::exact(0), parallel::Chan {});
+    registry.builtin("SEND", Arity::exact(2), parallel::SendValue {});
+    registry.builtin("RECV", Arity::exact(1), parallel::Recv {});
+    registry.builtin("FLOAT", Arity::exact(1), type_converter::TypeConverter {
+        op: Value::to_float,
+    });
+    registry.builtin("STRING", Arity::exact(1), type_converter::TypeConverter {
+        op: Value::to_string,
+    });
+    registry.builtin("INT", Arity::exact(1), type_converter::TypeConverter {
+        op: Value::to_integer,
+    });
+    registry.builtin("ARRAY", Arity::exact(1), type_converter::TypeConverter {
+        op: |l: &Value| {
+            Ok(Value::array(match l {
+                Value::Integer(_) => Vec::<Value>::new(),
+                Value::Float(_) => Vec::<Value>::new(),
+                Value::Boolean(_) => Vec::<Value>::new(),
+                Value::String(_) => Vec::<Value>::new(),
+                Value::Array(_) => Vec::<Value>::new(),
+                Value::Map(_) => Vec::<Value>::new(),
+                Value::Null => Vec::<Value>::new(),
+            }))
+        }
+    });
+    registry.builtin("VOID", Arity::exact(1), type_converter::TypeConverter {
+        op: |_| Ok(Value::Null),
+    });
+    registry.builtin("IS_NULL", Arity::exact(1), type_converter::TypeConverter {
+        op: |l: &Value| Ok(Value::Boolean(l.is_null())),
+    });
+    registry.builtin("+", Arity::exact(2), expression::Expression { op: Value::add });
+    registry.builtin("-", Arity::exact(2), expression::Expression {
+        op: Value::subtract,
+    });
+    registry.builtin("/", Arity::exact(2), expression::Division {});
+    registry.builtin("*", Arity::exact(2), expression::Expression {
+        op: Value::multiply,
+    });
+    registry.builtin("^", Arity::exact(2), expression::Expression { op: Value::power });
+    registry.builtin("=", Arity::exact(2), expression::Expression { op: Value::eq });
+    registry.builtin("<", Arity::exact(2), expression::Expression { op: Value::less });
+    registry.builtin(">", Arity::exact(2), expression::Expression { op: Value::more });
+    registry.builtin("??", Arity::exact(2), expression::Expression {
+        op: Value::coalesce,
+    });
 }
@@ -0,0 +1,144 @@
+use crate::compiler::Compiler;
+use crate::lexer::Token;
+use crate::parser::{Node, NodeType, Parser};
+
+// PROC ($CONTAINER, args...) writes the result back into $CONTAINER,
+// PROC ($CONTAINER, args...) $OTHER writes it into $OTHER instead
+pub fn parse_mutation(token: Token, parser: &mut Parser) -> Result<Node, String> {
+    let mut params = parser.subparse_list_in_bracers(None)?;
+
+    let target = if parser.peek().is_some_and(|t| t.starts_with("$")) {
+        parser.subparse_variable_name()?
+    } else {
+        match params.first() {
+            Some(node) if node.node_type == NodeType::Variable => Node::new_binding(node.value.clone(), node.token_position),
+            _ => return Err(format!("{} expects a variable as the first argument", token.value)),
+        }
+    };
+
+    params.push(target);
+
+    Ok(Node::new_operation(token.value, params, token.at))
+}
+
+// the same procedure used inside an expression has no target and just returns the result
+pub fn compile_mutation(sc: &mut Compiler, node: Node) -> Result<(), String> {
+    if !is_mutation(&node) {
+        return sc.sub_compile(node);
+    }
+
+    let (target, args) = node.params.split_last().unwrap();
+    let Some((container, rest)) = args.split_first() else {
+        return Err(format!("{} expects arguments", node.value));
+    };
+
+    // the container is moved out of its variable and written back by SET,
+    // unless the other arguments still have to read it or may run a flow that reads it
+    let moved = container.node_type == NodeType::Variable
+        && container.value == target.value
+        && !rest.iter().any(|arg| reads(arg, &target.value) || may_run_flow(arg));
+
+    if moved {
+        sc.program.new_take(container.value.clone());
+    } else {
+        sc.compile(container.clone())?;
+    }
+
+    for arg in rest {
+        sc.compile(arg.clone())?;
+    }
+
+    sc.program.new_exec(node.value.clone(), args.len());
+    sc.program.new_set(target.value.clone());
+
+    Ok(())
+}
+
+pub fn is_mutation(node: &Node) -> bool {
+    node.params.last().is_some_and(|n| n.node_type == NodeType::Binding)
+}
+
+fn reads(node: &Node, variable: &str) -> bool {
+    (node.node_type == NodeType::Variable && node.value == variable) || node.params.iter().any(|param| reads(param, variable))
+}
+
+// a flow link or a procedure call, a flow name can also come from a variable
+fn may_run_flow(node: &Node) -> bool {
+    let calls = match node.node_type {
+        NodeType::FlowLink => true,
+        NodeType::Operation => !node.is_mathematical_operation() && !matches!(node.value.as_str(), "LIST" | "DICT" | "AT"),
+        _ => false,
+    };
+
+    calls || node.params.iter().any(may_run_flow)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Value;
+    use crate::vm::testing::{compile, integer, run};
+    use crate::vm::{Progress, VM};
+
+    #[test]
+    fn test_copies_share_values_until_mutated() {
+        let memo = run("#MAIN() void
+var ({\"a\": 1}) $A
+var ($A) $B
+set ($B, \"b\", 2)
+var (len($A)) $A_LEN
+var (len($B)) $B_LEN").unwrap();
+
+        assert_eq!(integer(&memo, "$A_LEN"), 1);
+        assert_eq!(integer(&memo, "$B_LEN"), 2);
+    }
+
+    #[test]
+    fn test_mutation_keeps_variable_for_callbacks() {
+        let memo = run("#MAIN() void
+var ([1, 2]) $A
+push ($A, map([1], #SIZE))
+var (len($A)) $LEN
+#SIZE($X) int
+return (len($A))").unwrap();
+
+        assert_eq!(integer(&memo, "$LEN"), 3);
+        assert_eq!(memo.get("$A").unwrap(), &Value::from(vec![Value::from(1), Value::from(2), Value::from(vec![Value::from(2)])]));
+    }
+
+    #[test]
+    fn test_caught_error_gives_the_taken_variable_back() {
+        let memo = run("#MAIN() void
+var ([1, 2]) $A
+var ({\"k\": 1}) $M
+try { push ($A, 1 / 0) } catch { }
+try { insert ($A, 9, 0) } catch { }
+try { remove ($M, [1]) } catch { }
+try { set ($M, \"k\", 2)
+pop ($A)
+push ($A, int(\"x\")) } catch { }
+try #GROW () $G catch #NOTHING
+#GROW() void
+push ($A, 1 / 0)
+#NOTHING($E) void").unwrap();
+
+        // mutations that finished before the error stay
+        assert_eq!(memo.get("$A").unwrap(), &Value::from(vec![Value::from(1)]));
+        assert_eq!(memo.get("$M").unwrap().repr(), "{k:2}");
+    }
+
+    #[test]
+    fn test_mutation_inside_try_moves_the_variable() {
+        let mut program = compile("#MAIN() void\nvar ([1, 2]) $A\ntry { push ($A, 3) } catch { }").unwrap();
+        let mut vm = VM::new();
+        vm.start(&mut program, "#MAIN", vec![]).unwrap();
+
+        // the array is not copied up front, TAKE moves it and the try only records that
+        let mut moved = false;
+        while vm.resume(&mut program, Some(1)).unwrap() == Progress::Yielded {
+            moved |= !program.state().taken.is_empty();
+        }
+
+        assert!(moved);
+        assert!(program.state().taken.is_empty());
+    }
+}
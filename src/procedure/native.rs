@@ -0,0 +1,185 @@
+use crate::compiler::Compiler;
+use crate::lexer::Token;
+use crate::parser::{Node, NodeType, Parser};
+use crate::procedure::Procedure;
+use crate::program::Value;
+use crate::vm::{Runtime, Stack};
+use std::any::Any;
+use std::fmt::Display;
+use std::marker::PhantomData;
+
+// a host closure, Args are its parameter types
+pub trait NativeFn<Args>: Send + Sync + 'static {
+    const ARITY: usize;
+
+    fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, String>;
+}
+
+// a host closure that gets the context value of type T before its arguments
+pub trait ContextFn<T, Args>: Send + Sync + 'static {
+    const ARITY: usize;
+
+    fn call(&self, context: &mut T, name: &str, args: Vec<Value>) -> Result<Value, String>;
+}
+
+// what a host closure may return, plain values or results with a displayable error
+pub trait IntoResult {
+    fn into_result(self) -> Result<Value, String>;
+}
+
+impl<T: Into<Value>> IntoResult for T {
+    fn into_result(self) -> Result<Value, String> {
+        Ok(self.into())
+    }
+}
+
+impl<T: Into<Value>, E: Display> IntoResult for Result<T, E> {
+    fn into_result(self) -> Result<Value, String> {
+        self.map(Into::into).map_err(|e| e.to_string())
+    }
+}
+
+macro_rules! native_fn {
+    ($count:expr $(, $arg:ident $value:ident)*) => {
+        impl<F, R $(, $arg)*> NativeFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: IntoResult,
+            $($arg: TryFrom<Value>, $arg::Error: Display,)*
+        {
+            const ARITY: usize = $count;
+
+            #[allow(unused_variables, unused_mut)]
+            fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+                let mut args = args.into_iter().enumerate();
+
+                $(
+                    let (i, value) = args.next().unwrap();
+                    let $value = $arg::try_from(value).map_err(|e| format!("{name} argument {}: {e}", i + 1))?;
+                )*
+
+                self($($value),*).into_result()
+            }
+        }
+
+        impl<T, F, R $(, $arg)*> ContextFn<T, ($($arg,)*)> for F
+        where
+            F: Fn(&mut T $(, $arg)*) -> R + Send + Sync + 'static,
+            R: IntoResult,
+            $($arg: TryFrom<Value>, $arg::Error: Display,)*
+        {
+            const ARITY: usize = $count;
+
+            #[allow(unused_variables, unused_mut)]
+            fn call(&self, context: &mut T, name: &str, args: Vec<Value>) -> Result<Value, String> {
+                let mut args = args.into_iter().enumerate();
+
+                $(
+                    let (i, value) = args.next().unwrap();
+                    let $value = $arg::try_from(value).map_err(|e| format!("{name} argument {}: {e}", i + 1))?;
+                )*
+
+                self(context $(, $value)*).into_result()
+            }
+        }
+    };
+}
+
+native_fn!(0);
+native_fn!(1, A a);
+native_fn!(2, A a, B b);
+native_fn!(3, A a, B b, C c);
+native_fn!(4, A a, B b, C c, D d);
+native_fn!(5, A a, B b, C c, D d, E e);
+
+// how a registered closure is called
+pub trait Invoke: Send + Sync + 'static {
+    fn invoke(&self, name: &str, args: Vec<Value>, rt: &mut Runtime) -> Result<Value, String>;
+}
+
+pub struct Plain<F, Args> {
+    function: F,
+    args: PhantomData<fn(Args)>,
+}
+
+impl<F, Args> Plain<F, Args> {
+    pub fn new(function: F) -> Self {
+        Plain { function, args: PhantomData }
+    }
+}
+
+impl<Args: 'static, F: NativeFn<Args>> Invoke for Plain<F, Args> {
+    fn invoke(&self, name: &str, args: Vec<Value>, _: &mut Runtime) -> Result<Value, String> {
+        self.function.call(name, args)
+    }
+}
+
+pub struct WithContext<T, F, Args> {
+    function: F,
+    args: PhantomData<fn(T, Args)>,
+}
+
+impl<T, F, Args> WithContext<T, F, Args> {
+    pub fn new(function: F) -> Self {
+        WithContext { function, args: PhantomData }
+    }
+}
+
+impl<T: Any + Send, Args: 'static, F: ContextFn<T, Args>> Invoke for WithContext<T, F, Args> {
+    fn invoke(&self, name: &str, args: Vec<Value>, rt: &mut Runtime) -> Result<Value, String> {
+        self.function.call(rt.context().require::<T>()?, name, args)
+    }
+}
+
+pub struct Native<I> {
+    name: String,
+    invoke: I,
+}
+
+impl<I> Native<I> {
+    pub fn new(name: String, invoke: I) -> Self {
+        Native { name, invoke }
+    }
+}
+
+impl<I: Invoke> Procedure for Native<I> {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // HTTP.GET ("https://example.com") $PAGE
+        parse_with_target(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_with_target(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        let mut args: Vec<Value> = (0..argc).map(|_| stack.pop()).collect();
+        args.reverse();
+
+        stack.push(self.invoke.invoke(&self.name, args, rt)?);
+
+        Ok(())
+    }
+}
+
+// NAME (args) $TARGET, the statement form of a procedure that returns a value
+pub fn parse_with_target(token: Token, parser: &mut Parser) -> Result<Node, String> {
+    let mut params = parser.subparse_list_in_bracers(None)?;
+    params.push(parser.subparse_variable_name()?);
+
+    Ok(Node::new_operation(token.value, params, token.at))
+}
+
+// inside of an expression there is no target, the value stays on the stack
+pub fn compile_with_target(sc: &mut Compiler, node: Node) -> Result<(), String> {
+    let Some((target, args)) = node.params.split_last().filter(|(target, _)| target.node_type == NodeType::Binding) else {
+        return sc.sub_compile(node);
+    };
+
+    for arg in args {
+        sc.compile(arg.clone())?;
+    }
+
+    sc.program.new_exec(node.value.clone(), args.len());
+    sc.program.new_var(target.value.clone());
+
+    Ok(())
+}
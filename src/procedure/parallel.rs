@@ -0,0 +1,283 @@
+use crate::compiler::Compiler;
+use crate::lexer::Token;
+use crate::parser::{Node, Parser};
+use crate::procedure::array::pop_integer;
+use crate::procedure::higher_order::pop_flow;
+use crate::procedure::native::{compile_with_target, parse_with_target};
+use crate::procedure::string;
+use crate::procedure::Procedure;
+use crate::program::Value;
+use crate::vm::{Runtime, Stack};
+
+pub struct Spawn {}
+
+impl Procedure for Spawn {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // SPAWN #FLOW (1, 2) $HANDLE
+        let link = parser.subparse_flow_link()?;
+
+        let mut params = vec![link];
+        params.extend(parser.subparse_list_in_bracers(None)?);
+        params.push(parser.subparse_variable_name()?);
+
+        Ok(Node::new_operation(token.value, params, token.at))
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_with_target(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        if argc == 0 {
+            return Err(String::from("argument count must be at least 1"));
+        }
+
+        let mut args: Vec<Value> = (1..argc).map(|_| stack.pop()).collect();
+        args.reverse();
+        let flow = pop_flow(stack)?;
+
+        let handle = rt.spawn(&flow, args)?;
+
+        stack.push(Value::Integer(handle as i64));
+
+        Ok(())
+    }
+}
+
+pub struct Join {}
+
+impl Procedure for Join {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // JOIN ($HANDLE) $RESULT waits for the flow and gives what it returned,
+        // JOIN ($ARRAY, ", ") $TEXT joins the items like the string procedure, AWAIT takes only a handle
+        parse_with_target(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_with_target(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        match argc {
+            1 => {}
+            2 => return string::Join {}.execute(argc, stack, rt),
+            _ => return Err(String::from("argument count must be 1 or 2")),
+        }
+
+        let handle = pop_index(stack, "task")?;
+
+        // the handle stays for the next try
+        match rt.join(handle)? {
+            Some(value) => stack.push(value),
+            None => stack.push(Value::Integer(handle as i64)),
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Chan {}
+
+impl Procedure for Chan {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // CHAN () $CHANNEL
+        parse_with_target(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_with_target(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        if argc != 0 {
+            return Err(String::from("argument count must be 0"));
+        }
+
+        stack.push(Value::Integer(rt.channel() as i64));
+
+        Ok(())
+    }
+}
+
+pub struct SendValue {}
+
+impl Procedure for SendValue {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // SEND ($CHANNEL, value), it does not wait for a receiver
+        let params = parser.subparse_list_in_bracers(Some(2))?;
+
+        Ok(Node::new_operation(token.value, params, token.at))
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let value = stack.pop();
+        let channel = pop_index(stack, "channel")?;
+
+        rt.send(channel, value)
+    }
+}
+
+pub struct Recv {}
+
+impl Procedure for Recv {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // RECV ($CHANNEL) $VALUE, waits until a value is sent
+        parse_with_target(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_with_target(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let channel = pop_index(stack, "channel")?;
+
+        match rt.recv(channel)? {
+            Some(value) => stack.push(value),
+            None => stack.push(Value::Integer(channel as i64)),
+        }
+
+        Ok(())
+    }
+}
+
+fn pop_index(stack: &mut Stack, what: &str) -> Result<usize, String> {
+    let index = pop_integer(stack)?;
+
+    usize::try_from(index).map_err(|_| format!("{what} {index} is not valid"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{Engine, RuntimeError};
+    use crate::program::Value;
+    use crate::vm::testing::compile_script;
+    use crate::vm::{Limits, Scheduler};
+    use std::time::Duration;
+
+    #[test]
+    fn test_parallel_flows() {
+        let source = "#MAIN() array
+chan () $C
+spawn #PRODUCER ($C, 3) $P
+spawn #SQUARE (4) $S
+var ([]) $OUT
+for (0, 3) $I #RECEIVE
+join ($P) $SENT
+push ($OUT, $SENT, join($S))
+return ($OUT)
+#RECEIVE() void
+recv ($C) $V
+push ($OUT, $V)
+#PRODUCER($C, $N) int
+for (0, $N) $I #SEND
+return ($N)
+#SEND() void
+send ($C, $I * 10)
+#SQUARE($X) int
+return ($X * $X)";
+        let expected: Vec<Value> = [0, 10, 20, 3, 16].into_iter().map(Value::from).collect();
+
+        for scheduler in [Scheduler::Threads, Scheduler::Deterministic] {
+            let mut script = compile_script(source);
+            script.set_scheduler(scheduler);
+
+            assert_eq!(script.run("main", vec![]).unwrap(), Value::from(expected.clone()));
+        }
+
+        let mut script = compile_script("#MAIN() void\nspawn #FAIL () $F\njoin ($F) $X\n#FAIL() int\nreturn (1 / 0)");
+        assert_eq!(script.run("main", vec![]).unwrap_err().message(), "flow #FAIL failed: division by zero");
+
+        // a panic of a host procedure on the thread of the flow still reaches the await
+        let mut engine = Engine::new();
+        engine.procedures().register_fn("explode", |x: i64| -> i64 { panic!("explode {x}") }).unwrap();
+        let mut script = engine.compile("#MAIN() void\nspawn #FAIL () $F\nawait ($F) $X\n#FAIL() int\nreturn (explode(1))").unwrap();
+        assert_eq!(script.run("main", vec![]).unwrap_err().message(), "flow #FAIL failed: the flow panicked: explode 1");
+
+        // tasks and channels are numbered together, a handle of one is not the other
+        let mut script = compile_script("#MAIN() void\nchan () $C\njoin ($C) $X");
+        assert_eq!(script.run("main", vec![]).unwrap_err().message(), "task 0 is not spawned");
+        let mut script = compile_script("#MAIN() void\nchan () $C\nspawn #NOTHING () $T\nrecv ($T) $X\n#NOTHING() void\nvar (1) $X");
+        assert_eq!(script.run("main", vec![]).unwrap_err().message(), "channel 1 is not open");
+        assert_eq!(compile_script("#MAIN() void\njoin (7) $X").run("main", vec![]).unwrap_err().message(), "task 7 is not spawned");
+
+        // spawned flows see the context of the host with both schedulers
+        let mut engine = Engine::new();
+        engine
+            .procedures()
+            .register_context_fn("visit", |visits: &mut Vec<i64>, page: i64| {
+                visits.push(page);
+                visits.len() as i64
+            })
+            .unwrap();
+        for scheduler in [Scheduler::Threads, Scheduler::Deterministic] {
+            let mut script = engine.compile("#MAIN() int\nspawn #VISIT (1) $A\nspawn #VISIT (2) $B\nreturn (join($A) + join($B))\n#VISIT($P) int\nreturn (visit($P))").unwrap();
+            script.set_scheduler(scheduler);
+            script.context().insert(Vec::<i64>::new());
+
+            assert_eq!(script.run("main", vec![]).unwrap(), Value::Integer(3));
+            assert_eq!(script.context().remove::<Vec<i64>>().unwrap().len(), 2);
+        }
+
+        // with two arguments join is the string procedure
+        let mut script = compile_script("#MAIN() string\njoin ([1, 2], \"-\") $J\nreturn ($J)");
+        assert_eq!(script.run("main", vec![]).unwrap(), Value::string("1-2"));
+
+        let mut script = compile_script("#MAIN() void\nfor (0, 10) $I #SPAWN\n#SPAWN() void\nspawn #NOTHING () $T\n#NOTHING() void\nvar (1) $X");
+        script.set_limits(Limits { max_tasks: Some(3), ..Limits::default() });
+        assert_eq!(script.run("main", vec![]).unwrap_err(), RuntimeError::TooManyTasks(3));
+
+        // nothing is able to send or finish once every flow waits, with both schedulers,
+        // with threads the flow that waits last sees it first, the error may come through a join
+        let blocked = [
+            "#MAIN() void\nchan () $C\nrecv ($C) $V",
+            "#MAIN() void\nchan () $C\nspawn #WAIT ($C) $W\njoin ($W) $X\n#WAIT($C) int\nrecv ($C) $V\nreturn ($V)",
+            "#MAIN() void\nchan () $C\nspawn #NOTHING () $N\nrecv ($C) $V\n#NOTHING() void\nvar (1) $X",
+        ];
+        for source in blocked {
+            for scheduler in [Scheduler::Threads, Scheduler::Deterministic] {
+                let mut script = compile_script(source);
+                script.set_scheduler(scheduler);
+                assert!(script.run("main", vec![]).unwrap_err().message().ends_with("flows are blocked waiting for each other"));
+            }
+        }
+
+        // a thread that waits for a slow sender is not blocked
+        let mut script = compile_script("#MAIN() int\nchan () $C\nspawn #SLOW ($C) $S\nrecv ($C) $V\nreturn ($V)\n#SLOW($C) void\nfor (0, 20000) $I #NOTHING\nsend ($C, 7)\n#NOTHING() void\nvar (1) $X");
+        script.set_limits(Limits { timeout: Some(Duration::from_secs(10)), ..Limits::default() });
+        assert_eq!(script.run("main", vec![]).unwrap(), Value::Integer(7));
+    }
+
+    #[test]
+    fn test_deterministic_scheduler() {
+        let source = "#MAIN() array
+chan () $C
+spawn #WORKER ($C, 1) $A
+spawn #WORKER ($C, 2) $B
+var ([]) $OUT
+for (0, 20) $I #RECEIVE
+return ($OUT)
+#RECEIVE() void
+recv ($C) $V
+push ($OUT, $V)
+#WORKER($C, $ID) void
+for (0, 10) $I #SEND
+#SEND() void
+send ($C, $ID * 100 + rand_int(0, 100))";
+        let run = || {
+            let mut script = compile_script(source);
+            script.set_scheduler(Scheduler::Deterministic);
+            script.set_seed(3);
+            script.run("main", vec![]).unwrap()
+        };
+
+        let first = run();
+        let Value::Array(items) = &first else {
+            panic!("expected an array");
+        };
+
+        assert_eq!(items.len(), 20);
+        // the flows take turns, neither of them sends all of its values first
+        assert!(items[..10].iter().any(|item| matches!(item, Value::Integer(n) if *n >= 200)));
+        assert_eq!(run(), first);
+    }
+}
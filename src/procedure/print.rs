@@ -1,25 +1,59 @@
 use crate::lexer::Token;
 use crate::parser::{Node, Parser};
 use crate::procedure::Procedure;
-use crate::vm::Stack;
+use crate::vm::{Runtime, Stack};
 
-pub struct Print {}
+pub struct Print {
+    pub newline: bool,
+    pub error: bool,
+}
 
 impl Procedure for Print {
     fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
-        // PRINT (expression)
+        // PRINT (expression), PRINTLN (expression) ends the line
+        // EPRINT and EPRINTLN write to the error output
         let expr = parser.subparse_one_in_bracers()?;
 
         Ok(Node::new_operation(token.value, vec![expr], token.at))
     }
-    fn execute(&self, argc: usize, stack: &mut Stack) -> Result<(), String> {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
         if argc != 1 {
             return Err(String::from("argument count must be 1"));
         }
 
-        let _ = stack.pop();
-        // println!("{}", first_operand.repr());
+        let mut text = stack.pop().repr();
 
-        Ok(())
+        if self.newline {
+            text.push('\n');
+        }
+
+        match self.error {
+            true => rt.write_error(&text),
+            false => rt.write(&text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::VM;
+    use crate::vm::testing::{Buffer, run_on};
+
+    #[test]
+    fn test_print_writes_to_the_output() {
+        let (out, err) = (Buffer::default(), Buffer::default());
+        let mut vm = VM::new();
+        vm.set_output(Box::new(out.clone()));
+        vm.set_error_output(Box::new(err.clone()));
+
+        run_on(&mut vm, "#MAIN() void
+print (\"a\")
+print (1)
+println ([1, \"b\"])
+eprint (\"oops\")
+eprintln (null)").unwrap();
+
+        assert_eq!(out.text(), "a1[1,b]\n");
+        assert_eq!(err.text(), "oopsnull\n");
     }
-}
\ No newline at end of file
+}
@@ -1,34 +1,153 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::compiler::Compiler;
+use crate::lexer::Token;
+use crate::parser::{Node, Parser};
+use crate::procedure::array::{last_array, pop_array, pop_integer};
+use crate::procedure::mutation::{compile_mutation, parse_mutation};
 use crate::procedure::Procedure;
 use crate::program::Value;
-use crate::vm::Stack;
-use rand::{Rng, RngExt};
-use rand::rngs::SmallRng;
-use rand::{SeedableRng};
+use crate::vm::{Runtime, Stack};
+use rand::seq::{IndexedRandom, SliceRandom};
+use rand::RngExt;
+use std::sync::Arc;
 
-pub struct Rand {
-    rng: Rc<RefCell<SmallRng>>,
+pub struct Rand {}
+
+impl Procedure for Rand {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        if argc != 0 {
+            return Err(String::from("argument count must be zero"));
+        }
+
+        stack.push(Value::Float(rt.rng().random::<f64>()));
+
+        Ok(())
+    }
 }
 
-impl Rand {
-    pub(crate) fn new() -> Rand {
-        let mut rng = rand::rng();
+pub struct RandInt {}
 
-        Rand {
-            rng: Rc::new(RefCell::new(SmallRng::seed_from_u64(rng.next_u64()))),
+impl Procedure for RandInt {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // RAND_INT (min, max), max is exclusive
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
         }
+
+        let max = pop_integer(stack)?;
+        let min = pop_integer(stack)?;
+
+        if min >= max {
+            return Err(format!("rand_int range {min}..{max} is empty"));
+        }
+
+        stack.push(Value::Integer(rt.rng().random_range(min..max)));
+
+        Ok(())
     }
 }
 
-impl Procedure for Rand {
-    fn execute(&self, argc: usize, stack: &mut Stack) -> Result<(), String> {
-        if argc != 0 {
-            return Err(String::from("argument count must be zero"));
+pub struct Choice {}
+
+impl Procedure for Choice {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // CHOICE ($ARRAY)
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let array = pop_array(stack)?;
+
+        match array.choose(rt.rng()) {
+            Some(item) => stack.push(item.clone()),
+            None => return Err(String::from("unable to choose from an empty array")),
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Shuffle {}
+
+impl Procedure for Shuffle {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // SHUFFLE ($ARRAY)
+        parse_mutation(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        compile_mutation(sc, node)
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
         }
 
-        stack.push(Value::Float(self.rng.borrow_mut().random::<f64>()));
+        Arc::make_mut(last_array(stack)?).shuffle(rt.rng());
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+pub struct Sample {}
+
+impl Procedure for Sample {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // SAMPLE ($ARRAY, count) picks distinct positions
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let count = pop_integer(stack)?;
+        let array = pop_array(stack)?;
+
+        if count < 0 || count as usize > array.len() {
+            return Err(format!("unable to sample {count} items from array of length {}", array.len()));
+        }
+
+        stack.push(Value::array(array.sample(rt.rng(), count as usize).cloned().collect()));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::VM;
+    use crate::vm::testing::{Buffer, run, run_on};
+
+    #[test]
+    fn test_same_seed_gives_same_output() {
+        let source = "#MAIN() void
+var ([1, 2, 3, 4, 5]) $A
+shuffle ($A)
+println ([rand_int(0, 100), choice($A), sample($A, 2), $A])
+var (array(int(0))) $EMPTY
+fill_random ($EMPTY, 3, 0, 1000) $FILLED
+println ($FILLED)";
+
+        let output = |seed: u64| {
+            let out = Buffer::default();
+            let mut vm = VM::new();
+            vm.set_output(Box::new(out.clone()));
+            vm.set_seed(seed);
+
+            run_on(&mut vm, source).unwrap();
+            run_on(&mut vm, source).unwrap();
+
+            out.text()
+        };
+
+        let first = output(42);
+        let (one, two) = first.split_at(first.len() / 2);
+
+        assert_eq!(first, output(42));
+        assert_eq!(one, two);
+        assert_ne!(first, output(43));
+    }
+
+    #[test]
+    fn test_random_procedure_errors() {
+        assert_eq!(run("#MAIN() void\nvar (rand_int(1, 1)) $A").err().unwrap(), "rand_int range 1..1 is empty");
+        assert_eq!(run("#MAIN() void\nvar (choice([])) $A").err().unwrap(), "unable to choose from an empty array");
+        assert_eq!(run("#MAIN() void\nvar (sample([1], 2)) $A").err().unwrap(), "unable to sample 2 items from array of length 1");
+    }
+}
@@ -0,0 +1,142 @@
+use crate::procedure::native::{Native, Plain, WithContext};
+use crate::procedure::{register_builtins, ContextFn, NativeFn, Procedure};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+// how many arguments a procedure accepts, checked when the script is compiled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Arity {
+    min: usize,
+    max: Option<usize>,
+}
+
+impl Arity {
+    // statements and procedures that check their arguments themselves
+    pub fn any() -> Arity {
+        Arity { min: 0, max: None }
+    }
+    pub fn exact(count: usize) -> Arity {
+        Arity { min: count, max: Some(count) }
+    }
+    pub fn between(min: usize, max: usize) -> Arity {
+        Arity { min, max: Some(max) }
+    }
+    pub fn at_least(min: usize) -> Arity {
+        Arity { min, max: None }
+    }
+    pub fn accepts(&self, argc: usize) -> bool {
+        argc >= self.min && self.max.is_none_or(|max| argc <= max)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{max}"),
+            Some(max) => write!(f, "{} to {max}", self.min),
+            None => write!(f, "at least {}", self.min),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Registered {
+    pub procedure: Arc<dyn Procedure>,
+    pub arity: Arity,
+}
+
+// every procedure a script can use, the built-ins and the ones of the host
+#[derive(Clone)]
+pub struct ProcedureRegistry {
+    procedures: BTreeMap<String, Registered>,
+}
+
+impl Default for ProcedureRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcedureRegistry {
+    pub fn new() -> ProcedureRegistry {
+        let mut registry = ProcedureRegistry { procedures: BTreeMap::new() };
+
+        register_builtins(&mut registry);
+
+        registry
+    }
+    // names are case insensitive, dots separate namespaces: http.get
+    pub fn register(&mut self, name: &str, arity: Arity, procedure: impl Procedure + 'static) -> Result<(), String> {
+        let name = name.to_uppercase();
+
+        if !is_valid_name(&name) {
+            return Err(format!("{name} is not a valid procedure name"));
+        }
+        if self.procedures.contains_key(&name) {
+            return Err(format!("procedure {name} is already registered"));
+        }
+
+        self.procedures.insert(name, Registered { procedure: Arc::new(procedure), arity });
+
+        Ok(())
+    }
+    // arguments are converted to the closure parameter types, its arity is the parameter count
+    pub fn register_fn<Args: 'static, F: NativeFn<Args>>(&mut self, name: &str, function: F) -> Result<(), String> {
+        let native = Native::new(name.to_uppercase(), Plain::new(function));
+
+        self.register(name, Arity::exact(F::ARITY), native)
+    }
+    // the closure also gets the context value of type T, the run fails when the host did not provide it
+    pub fn register_context_fn<T: Any + Send, Args: 'static, F: ContextFn<T, Args>>(&mut self, name: &str, function: F) -> Result<(), String> {
+        let native = Native::new(name.to_uppercase(), WithContext::new(function));
+
+        self.register(name, Arity::exact(F::ARITY), native)
+    }
+    // registers everything under prefix.name
+    pub fn namespace(&mut self, prefix: &str) -> Namespace<'_> {
+        Namespace {
+            registry: self,
+            prefix: prefix.to_string(),
+        }
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.procedures.contains_key(&name.to_uppercase())
+    }
+    pub(crate) fn get(&self, name: &str) -> Result<&Registered, String> {
+        self.procedures.get(name).ok_or_else(|| format!("procedure {name} is not defined"))
+    }
+    pub(crate) fn builtin(&mut self, name: &str, arity: Arity, procedure: impl Procedure + 'static) {
+        self.procedures.insert(
+            name.to_string(),
+            Registered {
+                procedure: Arc::new(procedure),
+                arity,
+            },
+        );
+    }
+}
+
+pub struct Namespace<'a> {
+    registry: &'a mut ProcedureRegistry,
+    prefix: String,
+}
+
+impl Namespace<'_> {
+    pub fn register(&mut self, name: &str, arity: Arity, procedure: impl Procedure + 'static) -> Result<(), String> {
+        self.registry.register(&format!("{}.{name}", self.prefix), arity, procedure)
+    }
+    pub fn register_fn<Args: 'static, F: NativeFn<Args>>(&mut self, name: &str, function: F) -> Result<(), String> {
+        self.registry.register_fn(&format!("{}.{name}", self.prefix), function)
+    }
+    pub fn register_context_fn<T: Any + Send, Args: 'static, F: ContextFn<T, Args>>(&mut self, name: &str, function: F) -> Result<(), String> {
+        self.registry.register_context_fn(&format!("{}.{name}", self.prefix), function)
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    name.split('.').all(|part| {
+        part.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_') && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
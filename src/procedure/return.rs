@@ -15,6 +15,30 @@ impl Procedure for Return {
     fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
         let child = node.params.first().unwrap();
 
-        sc.sub_compile(child.clone())
+        sc.sub_compile(child.clone())?;
+        sc.program.new_ret();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::testing::{integer, run};
+
+    #[test]
+    fn test_return_leaves_the_flow() {
+        let memo = run("#MAIN() void
+var (0) $AFTER
+call #FIRST (4) $RESULT
+#FIRST(int($N)) int
+for (0, 3) $I #NOTHING
+if ($N > 2) { return ($N * 2) }
+set (1) $AFTER
+return (0)
+#NOTHING() void").unwrap();
+
+        assert_eq!(integer(&memo, "$RESULT"), 8);
+        assert_eq!(integer(&memo, "$AFTER"), 0);
     }
-}
\ No newline at end of file
+}
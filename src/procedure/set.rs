@@ -0,0 +1,54 @@
+use crate::compiler::Compiler;
+use crate::lexer::Token;
+use crate::parser::{Node, Parser};
+use crate::procedure::mutation::{compile_mutation, is_mutation, parse_mutation};
+use crate::procedure::array::resolve_index;
+use crate::procedure::Procedure;
+use crate::program::{Key, Value};
+use crate::vm::{Runtime, Stack};
+use std::sync::Arc;
+
+pub struct Set {}
+
+impl Procedure for Set {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // SET (expression) $VAR_NAME
+        // SET ($MAP, "key", expression)
+        // SET ($ARRAY, index, expression)
+        parse_mutation(token, parser)
+    }
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        if !is_mutation(&node) || node.params.len() != 2 {
+            return compile_mutation(sc, node);
+        }
+
+        sc.compile(node.params.first().unwrap().clone())?;
+        sc.program.new_set(node.params.last().unwrap().value.clone());
+
+        Ok(())
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        if argc != 3 {
+            return Err(String::from("argument count must be 3"));
+        }
+
+        let value = stack.pop();
+        let key = stack.pop();
+
+        // the container stays on the stack, a failed set leaves it as it was
+        match (stack.last_mut(), key) {
+            (Some(Value::Map(map)), key) => {
+                let key = Key::from_value(&key)?;
+                Arc::make_mut(map).insert(key, value);
+            }
+            (Some(Value::Array(array)), Value::Integer(index)) => {
+                let position = resolve_index(index, array.len())?;
+                Arc::make_mut(array)[position] = value;
+            }
+            (Some(Value::Array(_)), _) => return Err(String::from("array index must be an integer")),
+            _ => return Err(String::from("set expects a map or an array")),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use crate::procedure::array::{pop_array, pop_integer};
+use crate::procedure::Procedure;
+use crate::program::Value;
+use crate::vm::{Runtime, Stack};
+use std::sync::Arc;
+
+// strings are indexed by chars, not by bytes
+pub struct Transform {
+    pub op: fn(&str) -> String,
+}
+
+impl Procedure for Transform {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // UPPER ($STRING), LOWER ($STRING), TRIM ($STRING)
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let string = pop_string(stack)?;
+
+        stack.push(Value::string((self.op)(&string)));
+
+        Ok(())
+    }
+}
+
+pub struct Substr {}
+
+impl Procedure for Substr {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // SUBSTR ($STRING, start) or SUBSTR ($STRING, start, length), negative start counts from the end
+        if argc != 2 && argc != 3 {
+            return Err(String::from("argument count must be 2 or 3"));
+        }
+
+        let length = if argc == 3 { Some(pop_integer(stack)?) } else { None };
+        let start = pop_integer(stack)?;
+        let string = pop_string(stack)?;
+
+        let len = string.chars().count() as i64;
+        let from = if start < 0 { len + start } else { start };
+        let to = length.map_or(len, |length| from + length);
+
+        if from < 0 || from > len || length.is_some_and(|length| length < 0) || to > len {
+            return Err(format!("substr {start}, {} is out of range for string of length {len}", length.unwrap_or(len - from)));
+        }
+
+        let substring: String = string.chars().skip(from as usize).take((to - from) as usize).collect();
+
+        stack.push(Value::string(substring));
+
+        Ok(())
+    }
+}
+
+pub struct Split {}
+
+impl Procedure for Split {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // SPLIT ($STRING, ",")
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let separator = pop_string(stack)?;
+        let string = pop_string(stack)?;
+
+        if separator.is_empty() {
+            return Err(String::from("split separator must not be empty, use chars"));
+        }
+
+        stack.push(Value::array(string.split(separator.as_str()).map(Value::string).collect()));
+
+        Ok(())
+    }
+}
+
+pub struct Join {}
+
+impl Procedure for Join {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // JOIN ($ARRAY, ", ")
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let separator = pop_string(stack)?;
+        let array = pop_array(stack)?;
+
+        let parts: Vec<String> = array.iter().map(Value::repr).collect();
+
+        stack.push(Value::string(parts.join(separator.as_str())));
+
+        Ok(())
+    }
+}
+
+pub struct Replace {}
+
+impl Procedure for Replace {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // REPLACE ($STRING, "from", "to") replaces every occurrence
+        if argc != 3 {
+            return Err(String::from("argument count must be 3"));
+        }
+
+        let to = pop_string(stack)?;
+        let from = pop_string(stack)?;
+        let string = pop_string(stack)?;
+
+        if from.is_empty() {
+            return Err(String::from("replaced substring must not be empty"));
+        }
+
+        stack.push(Value::string(string.replace(from.as_str(), &to)));
+
+        Ok(())
+    }
+}
+
+pub struct Affix {
+    pub op: fn(&str, &str) -> bool,
+}
+
+impl Procedure for Affix {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // STARTS_WITH ($STRING, "prefix"), ENDS_WITH ($STRING, "suffix")
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let affix = pop_string(stack)?;
+        let string = pop_string(stack)?;
+
+        stack.push(Value::Boolean((self.op)(&string, &affix)));
+
+        Ok(())
+    }
+}
+
+pub struct Repeat {}
+
+impl Procedure for Repeat {
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        // REPEAT ($STRING, 3)
+        if argc != 2 {
+            return Err(String::from("argument count must be 2"));
+        }
+
+        let count = pop_integer(stack)?;
+        let string = pop_string(stack)?;
+
+        if count < 0 {
+            return Err(format!("unable to repeat a string {count} times"));
+        }
+
+        let len = string.len().saturating_mul(count as usize);
+        rt.check_len(len)?;
+
+        let mut repeated = String::new();
+        if repeated.try_reserve_exact(len).is_err() {
+            return Err(rt.too_large(len));
+        }
+        for _ in 0..count {
+            repeated.push_str(&string);
+        }
+
+        stack.push(Value::string(repeated));
+
+        Ok(())
+    }
+}
+
+pub struct Chars {}
+
+impl Procedure for Chars {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
+        // CHARS ($STRING) is an array of one char strings
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
+
+        let string = pop_string(stack)?;
+
+        stack.push(Value::array(string.chars().map(Value::string).collect()));
+
+        Ok(())
+    }
+}
+
+// position of the needle in chars
+pub fn char_index_of(string: &str, needle: &str) -> Option<usize> {
+    string.find(needle).map(|byte_idx| string[..byte_idx].chars().count())
+}
+
+pub fn pop_string(stack: &mut Stack) -> Result<Arc<String>, String> {
+    match stack.pop() {
+        Value::String(string) => Ok(string),
+        other => Err(format!("expected a string, got {}", other.repr())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Value;
+    use crate::vm::testing::{integer, run};
+
+    #[test]
+    fn test_string_procedures() {
+        let memo = run("#MAIN() void
+var (\"  Привет, мир  \") $RAW
+var (trim($RAW)) $S
+var (len($S)) $LEN
+var (substr($S, 8)) $WORLD
+var (substr($S, 0, 6)) $HELLO
+var (upper($WORLD)) $UPPER
+var (lower(\"ÄB\")) $LOWER
+var (split(\"a,b,,c\", \",\")) $PARTS
+var (join($PARTS, \"-\")) $JOINED
+var (replace($S, \"мир\", \"world\")) $REPLACED
+var (contains($S, \"мир\")) $CONTAINS
+var (starts_with($S, \"При\")) $STARTS
+var (ends_with($S, \"при\")) $ENDS
+var (index_of($S, \"мир\")) $AT
+var (repeat(\"ab\", 3)) $REPEATED
+var (chars(\"ёж\")) $CHARS").unwrap();
+
+        let string = |name: &str| memo.get(name).unwrap().repr();
+
+        assert_eq!(integer(&memo, "$LEN"), 11);
+        assert_eq!(string("$WORLD"), "мир");
+        assert_eq!(string("$HELLO"), "Привет");
+        assert_eq!(string("$UPPER"), "МИР");
+        assert_eq!(string("$LOWER"), "äb");
+        assert_eq!(string("$PARTS"), "[a,b,,c]");
+        assert_eq!(string("$JOINED"), "a-b--c");
+        assert_eq!(string("$REPLACED"), "Привет, world");
+        assert_eq!(memo.get("$CONTAINS"), Some(&Value::Boolean(true)));
+        assert_eq!(memo.get("$STARTS"), Some(&Value::Boolean(true)));
+        assert_eq!(memo.get("$ENDS"), Some(&Value::Boolean(false)));
+        assert_eq!(integer(&memo, "$AT"), 8);
+        assert_eq!(string("$REPEATED"), "ababab");
+        assert_eq!(string("$CHARS"), "[ё,ж]");
+    }
+
+    #[test]
+    fn test_string_procedure_errors() {
+        assert_eq!(run("#MAIN() void\nvar (upper(1)) $A").err().unwrap(), "expected a string, got 1");
+        assert_eq!(run("#MAIN() void\nvar (substr(\"ab\", 1, 5)) $A").err().unwrap(), "substr 1, 5 is out of range for string of length 2");
+        assert_eq!(run("#MAIN() void\nvar (contains(\"ab\", 1)) $A").err().unwrap(), "expected a string, got 1");
+        assert_eq!(run("#MAIN() void\nvar (repeat(\"ab\", -1)) $A").err().unwrap(), "unable to repeat a string -1 times");
+    }
+}
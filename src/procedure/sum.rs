@@ -1,11 +1,11 @@
 use crate::procedure::Procedure;
 use crate::program::Value;
-use crate::vm::Stack;
+use crate::vm::{Runtime, Stack};
 
 pub struct Sum {}
 
 impl Procedure for Sum {
-    fn execute(&self, argc: usize, stack: &mut Stack) -> Result<(), String> {
+    fn execute(&self, argc: usize, stack: &mut Stack, _: &mut Runtime) -> Result<(), String> {
         if argc == 0 {
             return Ok(());
         }
@@ -15,7 +15,7 @@ impl Procedure for Sum {
         for _ in 1..argc {
             let operand = stack.pop();
 
-            result = operand.add(&result);
+            result = operand.add(&result)?;
         }
 
         stack.push(result);
@@ -0,0 +1,209 @@
+use crate::compiler::Compiler;
+use crate::lexer::Token;
+use crate::parser::{Node, NodeType, Parser};
+use crate::procedure::r#if::compile_block;
+use crate::procedure::string::pop_string;
+use crate::procedure::Procedure;
+use crate::vm::{Runtime, Stack};
+
+pub struct Try {}
+
+impl Procedure for Try {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // TRY #RISKY (1, 2) $RESULT CATCH #HANDLER, the handler gets the error and gives the result
+        // TRY { ... } CATCH $ERROR { ... }
+        if parser.peek().is_some_and(|t| t.value == "{") {
+            return self.parse_blocks(token, parser);
+        }
+
+        let link = parser.subparse_flow_link()?;
+        let args = parser.subparse_list_in_bracers(None)?;
+        let variable = parser.subparse_variable_name()?;
+
+        skip_catch(parser)?;
+
+        let mut params = vec![link, variable, parser.subparse_flow_link()?];
+        params.extend(args);
+
+        Ok(Node::new_operation(token.value, params, token.at))
+    }
+    // TRY -> catch
+    // <args>
+    // JMP #RISKY
+    // RESULT
+    // ENDTRY
+    // SKIP -> end
+    // JMP #HANDLER    the error is its argument
+    // RESULT
+    // VAR $RESULT
+    fn compile(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        if node.params[0].is_block() {
+            return self.compile_blocks(sc, node);
+        }
+
+        let mut args = sc.nested();
+        for n in node.params.iter().skip(3) {
+            args.sub_compile(n.clone())?;
+        }
+
+        sc.program.new_try(args.program.len() + 5);
+        sc.program.merge(args.program);
+        sc.program.new_jmp(node.params[0].value.clone());
+        sc.program.new_result();
+        sc.program.new_endtry();
+        sc.program.new_skip(2);
+        sc.program.new_jmp(node.params[2].value.clone());
+        sc.program.new_result();
+        sc.program.new_var(node.params[1].value.clone());
+
+        Ok(())
+    }
+}
+
+impl Try {
+    fn parse_blocks(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        let block = parser.subparse_block()?;
+
+        skip_catch(parser)?;
+
+        // the error is dropped when the catch does not name it
+        let variable = match parser.peek() {
+            Some(t) if t.value.starts_with('$') => parser.subparse_variable_name()?,
+            _ => Node::new_null(token.at),
+        };
+
+        Ok(Node::new_operation(token.value, vec![block, variable, parser.subparse_block()?], token.at))
+    }
+    // TRY -> catch
+    // <try block>
+    // ENDTRY
+    // SKIP -> end
+    // BIND $ERROR   or DROP without a name
+    // <catch block>
+    fn compile_blocks(&self, sc: &mut Compiler, node: Node) -> Result<(), String> {
+        let risky = compile_block(sc, &node.params[0])?;
+        let handler = compile_block(sc, &node.params[2])?;
+
+        sc.program.new_try(risky.len() + 3);
+        sc.program.merge(risky);
+        sc.program.new_endtry();
+        sc.program.new_skip(handler.len() + 1);
+        match node.params[1].node_type {
+            NodeType::Null => sc.program.new_drop(),
+            _ => sc.program.new_bind(node.params[1].value.clone()),
+        }
+        sc.program.merge(handler);
+
+        Ok(())
+    }
+}
+
+pub struct Throw {}
+
+impl Procedure for Throw {
+    fn parse(&self, token: Token, parser: &mut Parser) -> Result<Node, String> {
+        // THROW ("message") or THROW ("message", "kind"), the kind is "error" when it is not given
+        let params = parser.subparse_list_in_bracers(None)?;
+
+        Ok(Node::new_operation(token.value, params, token.at))
+    }
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        let kind = match argc {
+            1 => String::from("error"),
+            2 => pop_string(stack)?.to_string(),
+            _ => return Err(String::from("argument count must be 1 or 2")),
+        };
+        let message = pop_string(stack)?.to_string();
+
+        Err(rt.fail(kind, message))
+    }
+}
+
+fn skip_catch(parser: &mut Parser) -> Result<(), String> {
+    if !parser.peek().is_some_and(|t| t.value.eq_ignore_ascii_case("catch")) {
+        return Err("try must be followed by catch".to_string());
+    }
+
+    parser.skip_token();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::RuntimeError;
+    use crate::program::Value;
+    use crate::vm::{Limits, Scheduler};
+    use crate::vm::testing::compile_script;
+
+    #[test]
+    fn test_try_and_throw() {
+        let source = "#MAIN() array
+var ([]) $OUT
+try #DIVIDE (1, 0) $Q catch #HANDLER
+push ($OUT, $Q)
+try { var (int(\"abc\")) $N } catch $E { push ($OUT, get($E, \"kind\")) }
+try { throw (\"bad input\", \"input\") } catch $E { push ($OUT, get($E, \"message\"), get($E, \"kind\")) }
+try { push ($OUT, 1 / 0) } catch { push ($OUT, len($OUT)) }
+var (map([1, 0], #SAFE)) $SAFE
+push ($OUT, $SAFE)
+try { var (map([1, 0], #INVERT)) $X } catch $E { push ($OUT, get($E, \"backtrace\")) }
+return ($OUT)
+#DIVIDE($A, $B) int
+return ($A / $B)
+#HANDLER($E) string
+return (get($E, \"message\"))
+#SAFE($X) int
+try #INVERT ($X) $R catch #ZERO
+return ($R)
+#INVERT($X) int
+return (1 / $X)
+#ZERO($E) int
+return (0)";
+        let mut script = compile_script(source);
+        let expected = Value::from(vec![
+            Value::from("division by zero"),
+            Value::from("conversion"),
+            Value::from("bad input"),
+            Value::from("input"),
+            Value::from(4),
+            Value::from(vec![Value::from(1), Value::from(0)]),
+            Value::from(vec![Value::from("#INVERT"), Value::from("#MAIN")]),
+        ]);
+        assert_eq!(script.run("main", vec![]).unwrap(), expected);
+
+        // built-ins give the kind with the error, through callbacks and joins too
+        let source = "#MAIN() array
+var ([]) $OUT
+try { var (1 / 0) $X } catch $E { push ($OUT, get($E, \"kind\")) }
+try { var (float(\"x\")) $X } catch $E { push ($OUT, get($E, \"kind\")) }
+try { var (len(1)) $X } catch $E { push ($OUT, get($E, \"kind\")) }
+try { throw (\"division by zero\") } catch $E { push ($OUT, get($E, \"kind\")) }
+try { var (map([0], #INVERT)) $X } catch $E { push ($OUT, get($E, \"kind\")) }
+spawn #INVERT (0) $T
+try { join ($T) $X } catch $E { push ($OUT, get($E, \"kind\")) }
+return ($OUT)
+#INVERT($X) int
+return (1 / $X)";
+        let expected = Value::from(
+            ["division_by_zero", "conversion", "runtime", "error", "division_by_zero", "division_by_zero"].into_iter().map(Value::from).collect::<Vec<_>>(),
+        );
+        for scheduler in [Scheduler::Threads, Scheduler::Deterministic] {
+            let mut script = compile_script(source);
+            script.set_scheduler(scheduler);
+            assert_eq!(script.run("main", vec![]).unwrap(), expected);
+        }
+
+        // integer overflow is an error of the script, not of the host
+        let mut script = compile_script("#MAIN() string\ntry #GROW () $X catch #HANDLER\nreturn ($X)\n#GROW() int\nreturn (9223372036854775807 + 1)\n#HANDLER($E) string\nreturn (get($E, \"message\"))");
+        assert_eq!(script.run("main", vec![]).unwrap(), Value::from("9223372036854775807 + 1 overflows an integer"));
+
+        let mut script = compile_script("#MAIN() void\nthrow (\"boom\")");
+        assert_eq!(script.run("main", vec![]).unwrap_err(), RuntimeError::Failed(String::from("boom")));
+
+        // limits stop the run, a catch does not see them
+        let mut script = compile_script("#MAIN() void\ntry { while (1 > 0) #SPIN } catch { var (1) $X }\n#SPIN() void\nvar (1) $Y");
+        script.set_limits(Limits { fuel: Some(500), ..Limits::default() });
+        assert_eq!(script.run("main", vec![]).unwrap_err(), RuntimeError::OutOfFuel(500));
+    }
+}
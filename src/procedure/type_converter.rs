@@ -1,18 +1,20 @@
 use crate::procedure::Procedure;
 use crate::program::Value;
-use crate::vm::Stack;
+use crate::vm::{Runtime, Stack};
 
 pub struct TypeConverter {
-    pub op: fn(l: &Value) -> Value,
+    pub op: fn(l: &Value) -> Result<Value, String>,
 }
 
 impl Procedure for TypeConverter {
-    fn execute(&self, argc: usize, stack: &mut Stack) -> Result<(), String> {
-        assert_eq!(argc, 1, "Procedure expects 1 arguments");
+    fn execute(&self, argc: usize, stack: &mut Stack, rt: &mut Runtime) -> Result<(), String> {
+        if argc != 1 {
+            return Err(String::from("argument count must be 1"));
+        }
 
         let first_operand = stack.pop();
 
-        let new_value = (self.op)(&first_operand);
+        let new_value = (self.op)(&first_operand).map_err(|e| rt.fail("conversion", e))?;
 
         stack.push(new_value);
 
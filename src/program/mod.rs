@@ -1,5 +1,5 @@
 mod prog;
 mod value;
 
-pub use crate::program::prog::{Operation, Program};
-pub use crate::program::value::Value;
+pub use crate::program::prog::{Coroutine, Handler, LoopRecord, Operation, Program, State, Taken};
+pub use crate::program::value::{Key, Value};
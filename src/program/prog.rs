@@ -1,4 +1,7 @@
 use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use crate::procedure::{Procedure, ProcedureRegistry};
 use crate::program::Value;
 
 type OperationName = &'static str;
@@ -10,12 +13,30 @@ const JMP: OperationName = "JMP";
 const VAR: OperationName = "VAR";
 const CSKIP: OperationName = "CSKIP";
 const SKIP: OperationName = "SKIP";
+const BSKIP: OperationName = "BSKIP";
+const SET: OperationName = "SET";
+const BIND: OperationName = "BIND";
+const LOOP: OperationName = "LOOP";
+const ENDLOOP: OperationName = "ENDLOOP";
+const CASE: OperationName = "CASE";
+const BREAK: OperationName = "BREAK";
+const CONTINUE: OperationName = "CONTINUE";
+const RET: OperationName = "RET";
+const RESULT: OperationName = "RESULT";
+const TAKE: OperationName = "TAKE";
+const YIELD: OperationName = "YIELD";
+const DROP: OperationName = "DROP";
+const TRY: OperationName = "TRY";
+const ENDTRY: OperationName = "ENDTRY";
 
+#[derive(Clone)]
 pub struct Operation {
     pub name: OperationName,
     pub count: Option<usize>,
     pub word: Option<String>,
     pub value: Option<Value>,
+    // resolved by the link step for EXEC
+    pub procedure: Option<Arc<dyn Procedure>>,
 }
 
 impl Operation {
@@ -25,6 +46,7 @@ impl Operation {
             value: Some(value),
             word: None,
             count: None,
+            procedure: None,
         }
     }
     pub fn new_word(name: OperationName, word: String) -> Self {
@@ -33,6 +55,16 @@ impl Operation {
             word: Some(word),
             count: None,
             value: None,
+            procedure: None,
+        }
+    }
+    pub fn new_empty(name: OperationName) -> Self {
+        Self {
+            name,
+            count: None,
+            value: None,
+            word: None,
+            procedure: None,
         }
     }
     pub fn new_count(name: OperationName, count: usize) -> Self {
@@ -41,6 +73,16 @@ impl Operation {
             count: Some(count),
             value: None,
             word: None,
+            procedure: None,
+        }
+    }
+    pub fn new_value_count(name: OperationName, value: Option<Value>, count: usize) -> Self {
+        Self {
+            name,
+            value,
+            count: Some(count),
+            word: None,
+            procedure: None,
         }
     }
     pub fn new_word_count(name: OperationName, word: String, count: usize) -> Self {
@@ -49,126 +91,459 @@ impl Operation {
             word: Some(word),
             count: Some(count),
             value: None,
+            procedure: None,
         }
     }
-    pub fn to_string(&self) -> String {
-        let mut sb = self.name.to_string();
+}
 
-        if self.word.is_some() {
-            sb.push_str(format!(" {}", self.word.clone().unwrap()).as_str());
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if let Some(word) = &self.word {
+            write!(f, " {word}")?;
         }
-        if self.value.is_some() {
-            sb.push_str(format!(" {}", self.value.clone().unwrap().repr()).as_str());
+        if let Some(value) = &self.value {
+            write!(f, " {}", value.repr())?;
         }
-        if self.count.is_some() {
-            sb.push_str(format!(" {}", self.count.unwrap().clone()).as_str());
+        if let Some(count) = self.count {
+            write!(f, " {count}")?;
         }
 
-        sb
+        Ok(())
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopRecord {
+    pub trace_depth: usize,
+    pub exit: usize,
+}
+
+// a try that has not finished, an error inside it continues at its catch
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handler {
+    pub catch: usize,
+    pub depth: usize,
+    pub loops: usize,
+    pub stack: usize,
+}
+
+// a variable TAKE moved onto the stack inside of a try, a caught error puts it back
+#[derive(Clone, Debug, PartialEq)]
+pub struct Taken {
+    pub name: String,
+    pub stack: usize,
+}
+
+// where a run of the program is, kept apart from the code so it can be saved and restored
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct State {
+    pub op_idx: usize,
+    pub trace: Vec<usize>,
+    pub loops: Vec<LoopRecord>,
+    // value of the last finished flow, taken by the caller
+    pub result: Option<Value>,
+    // value returned by the entry flow itself, it has no caller to take it
+    pub exit: Option<Value>,
+    // set by YIELD, whoever resumed this state takes it
+    pub yielded: Option<Value>,
+    // started by this state and not finished yet, handles are counted and never reused
+    pub coroutines: BTreeMap<usize, Coroutine>,
+    pub next_coroutine: usize,
+    pub handlers: Vec<Handler>,
+    // moved out by TAKE and not written back by SET yet
+    pub taken: Vec<Taken>,
+}
+
+// a flow that yields values, suspended with its own state, stack and variables
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coroutine {
+    pub state: State,
+    pub stack: Vec<Value>,
+    pub frames: Vec<BTreeMap<String, Value>>,
+    // the last yielded value
+    pub current: Option<Value>,
+}
+
 pub struct Program {
-    ops: Vec<Operation>,
+    // shared by the forks of the program
+    ops: Arc<Vec<Operation>>,
     marks: BTreeMap<String, usize>,
-    trace: Vec<usize>,
-    op_idx: usize
+    arities: BTreeMap<String, usize>,
+    state: State,
 }
 
 impl Program {
     pub fn new() -> Self {
         Program {
-            ops: vec![],
-            trace: Vec::with_capacity(255),
+            ops: Arc::new(vec![]),
             marks: BTreeMap::new(),
-            op_idx: 0
+            arities: BTreeMap::new(),
+            state: State {
+                trace: Vec::with_capacity(255),
+                ..State::default()
+            },
         }
     }
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
     pub fn merge(&mut self, prog: Program) {
-        self.ops.extend(prog.ops);
+        self.ops_mut().extend(Arc::unwrap_or_clone(prog.ops));
     }
-    pub fn new_mark(&mut self, name: String) {
-        self.ops.push(Operation::new_word(MARK, name.clone()));
+    pub fn new_mark(&mut self, name: String, arity: usize) {
+        self.ops_mut().push(Operation::new_word(MARK, name.clone()));
 
+        self.arities.insert(name.clone(), arity);
         self.marks.insert(name, self.ops.len() - 1);
     }
     pub fn new_push(&mut self, value: Value) {
-        self.ops.push(Operation::new_value(PUSH, value));
+        self.ops_mut().push(Operation::new_value(PUSH, value));
+    }
+    pub fn new_take(&mut self, name: String) {
+        self.ops_mut().push(Operation::new_word(TAKE, name));
     }
     pub fn new_var(&mut self, name: String) {
-        self.ops.push(Operation::new_word(VAR, name));
+        self.ops_mut().push(Operation::new_word(VAR, name));
     }
     pub fn new_jmp(&mut self, name: String) {
-        self.ops.push(Operation::new_word(JMP, name));
+        self.ops_mut().push(Operation::new_word(JMP, name));
     }
     pub fn new_cskip(&mut self, num: usize) {
-        self.ops.push(Operation::new_count(CSKIP, num));
+        self.ops_mut().push(Operation::new_count(CSKIP, num));
     }
     pub fn new_skip(&mut self, num: usize) {
-        self.ops.push(Operation::new_count(SKIP, num));
+        self.ops_mut().push(Operation::new_count(SKIP, num));
+    }
+    pub fn new_bskip(&mut self, num: usize) {
+        self.ops_mut().push(Operation::new_count(BSKIP, num));
+    }
+    pub fn new_set(&mut self, name: String) {
+        self.ops_mut().push(Operation::new_word(SET, name));
+    }
+    pub fn new_bind(&mut self, name: String) {
+        self.ops_mut().push(Operation::new_word(BIND, name));
+    }
+    pub fn new_loop(&mut self, exit: usize) {
+        self.ops_mut().push(Operation::new_count(LOOP, exit));
+    }
+    pub fn new_endloop(&mut self) {
+        self.ops_mut().push(Operation::new_empty(ENDLOOP));
+    }
+    // None is the wildcard, it matches anything
+    pub fn new_case(&mut self, value: Option<Value>, num: usize) {
+        self.ops_mut().push(Operation::new_value_count(CASE, value, num));
+    }
+    pub fn new_break(&mut self) {
+        self.ops_mut().push(Operation::new_empty(BREAK));
+    }
+    pub fn new_continue(&mut self) {
+        self.ops_mut().push(Operation::new_empty(CONTINUE));
+    }
+    pub fn new_ret(&mut self) {
+        self.ops_mut().push(Operation::new_empty(RET));
+    }
+    pub fn new_result(&mut self) {
+        self.ops_mut().push(Operation::new_empty(RESULT));
     }
     pub fn new_exec(&mut self, name: String, argc: usize) {
-        self.ops.push(Operation::new_word_count(EXEC, name, argc));
+        self.ops_mut().push(Operation::new_word_count(EXEC, name, argc));
     }
-    pub fn is_end(&self) -> bool {
-        self.op_idx > self.ops.len() - 1
+    pub fn new_yield(&mut self) {
+        self.ops_mut().push(Operation::new_empty(YIELD));
     }
-    pub fn finish_block(&mut self) {
-        self.op_idx = match self.trace.pop() {
-            Some(idx) => idx,
-            None => self.ops.len(),
-        };
+    pub fn new_drop(&mut self) {
+        self.ops_mut().push(Operation::new_empty(DROP));
     }
-    pub fn next(&mut self) {
-        self.op_idx += 1;
-
-        if !self.is_end() {
-            return;
+    pub fn new_try(&mut self, catch: usize) {
+        self.ops_mut().push(Operation::new_count(TRY, catch));
+    }
+    pub fn new_endtry(&mut self) {
+        self.ops_mut().push(Operation::new_empty(ENDTRY));
+    }
+    fn ops_mut(&mut self) -> &mut Vec<Operation> {
+        Arc::make_mut(&mut self.ops)
+    }
+    // the same code with a state of its own, for a flow on another thread
+    pub fn fork(&self) -> Program {
+        Program {
+            ops: self.ops.clone(),
+            marks: self.marks.clone(),
+            arities: self.arities.clone(),
+            state: State::default(),
+        }
+    }
+    pub fn operations(&self) -> &[Operation] {
+        &self.ops
+    }
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+    pub fn set_state(&mut self, state: State) {
+        self.state = state;
+    }
+    // puts another execution in place, the returned one continues after it is put back
+    pub fn replace_state(&mut self, state: State) -> State {
+        std::mem::replace(&mut self.state, state)
+    }
+    pub fn link(&mut self, procedures: &ProcedureRegistry) -> Result<(), String> {
+        for op in self.ops_mut().iter_mut().filter(|op| op.name == EXEC) {
+            op.procedure = Some(procedures.get(op.word.as_ref().unwrap())?.procedure.clone());
         }
 
-        self.finish_block();
+        Ok(())
+    }
+    pub fn is_end(&self) -> bool {
+        self.state.op_idx > self.ops.len() - 1
+    }
+    // returns true when control went back to the caller flow
+    pub fn finish_block(&mut self) -> bool {
+        self.state.result = None;
+
+        match self.state.trace.pop() {
+            Some(idx) => {
+                self.state.op_idx = idx;
+                true
+            }
+            None => {
+                self.state.op_idx = self.ops.len();
+                false
+            }
+        }
+    }
+    pub fn next(&mut self) {
+        self.state.op_idx += 1;
+    }
+    pub fn depth(&self) -> usize {
+        self.state.trace.len()
     }
     pub fn current(&self) -> Option<&Operation> {
         if self.is_end() {
             return None;
         }
 
-        self.ops.get(self.op_idx)
+        self.ops.get(self.state.op_idx)
     }
     pub fn trace_back(&mut self) {
-        self.trace.push(self.op_idx + 1);
+        self.state.trace.push(self.state.op_idx + 1);
     }
     pub fn skip(&mut self, num: usize) {
-        if num == 0 && self.op_idx > 0 {
-            self.op_idx -= 1;
-            return;
+        self.state.op_idx += num;
+    }
+    // compensates the next() call of the vm loop
+    pub fn step_back(&mut self) {
+        if self.state.op_idx > 0 {
+            self.state.op_idx -= 1;
+        }
+    }
+    pub fn skip_back(&mut self, num: usize) {
+        self.state.op_idx -= num;
+    }
+    pub fn enter_loop(&mut self, exit: usize) {
+        self.state.loops.push(LoopRecord {
+            trace_depth: self.state.trace.len(),
+            exit: self.state.op_idx + exit,
+        });
+    }
+    pub fn leave_loop(&mut self) {
+        self.state.loops.pop();
+    }
+    pub fn break_loop(&mut self) -> Result<(), String> {
+        let record = self.current_loop()?;
+        let (trace_depth, exit) = (record.trace_depth, record.exit);
+
+        self.state.trace.truncate(trace_depth);
+        self.state.handlers.retain(|handler| handler.depth <= trace_depth);
+        // ENDLOOP at the exit drops the record
+        self.state.op_idx = exit - 1;
+
+        Ok(())
+    }
+    pub fn continue_loop(&mut self) -> Result<(), String> {
+        let trace_depth = self.current_loop()?.trace_depth;
+
+        // finish the body flow as if it reached its end
+        self.state.trace.truncate(trace_depth + 1);
+        self.state.handlers.retain(|handler| handler.depth <= trace_depth);
+        self.finish_block();
+        self.step_back();
+
+        Ok(())
+    }
+    // leaves the current flow with a value, loops started inside it are dropped
+    pub fn return_flow(&mut self, value: Value) -> bool {
+        let depth = self.state.trace.len();
+        self.state.loops.retain(|record| record.trace_depth < depth);
+        self.state.handlers.retain(|handler| handler.depth < depth);
+
+        let returned = self.finish_block();
+        self.step_back();
+
+        match returned {
+            true => self.state.result = Some(value),
+            false => self.state.exit = Some(value),
+        }
+
+        returned
+    }
+    pub fn enter_try(&mut self, catch: usize, stack_size: usize) {
+        self.state.handlers.push(Handler {
+            catch: self.state.op_idx + catch,
+            depth: self.state.trace.len(),
+            loops: self.state.loops.len(),
+            stack: stack_size,
+        });
+    }
+    pub fn leave_try(&mut self) {
+        self.state.handlers.pop();
+    }
+    pub fn take_variable(&mut self, name: String, stack_size: usize) {
+        self.state.taken.push(Taken { name, stack: stack_size });
+    }
+    // SET wrote the taken variable back
+    pub fn settle_variable(&mut self, name: &str) {
+        if let Some(idx) = self.state.taken.iter().rposition(|taken| taken.name == name) {
+            self.state.taken.remove(idx);
         }
-        self.op_idx += num;
     }
-    pub fn jump_to_mark(&mut self, name: String) {
-        let name_clone = name.clone();
+    pub fn is_trying(&self) -> bool {
+        !self.state.handlers.is_empty()
+    }
+    // a try of the flow at the depth or of a flow it called
+    pub fn is_trying_from(&self, depth: usize) -> bool {
+        self.state.handlers.last().is_some_and(|handler| handler.depth >= depth)
+    }
+    // leaves everything the innermost try started, the next op is its catch,
+    // the variables taken inside of it are still on the stack above the handler
+    pub fn catch_error(&mut self) -> (Handler, Vec<Taken>) {
+        let handler = self.state.handlers.pop().unwrap();
+        let inside = self.state.taken.iter().position(|taken| taken.stack >= handler.stack).unwrap_or(self.state.taken.len());
+        let taken = self.state.taken.split_off(inside);
 
-        if let Some(op_id) = self.marks.get(&name_clone) {
-            self.op_idx = *op_id;
+        self.state.trace.truncate(handler.depth);
+        self.state.loops.truncate(handler.loops);
+        self.state.result = None;
+        self.state.yielded = None;
+        self.state.op_idx = handler.catch - 1;
 
-            return;
+        (handler, taken)
+    }
+    // flows from the one of the current op to the entry flow
+    pub fn backtrace(&self) -> Vec<String> {
+        // a trace entry is the op after the call
+        let calls = self.state.trace.iter().rev().map(|idx| idx - 1);
+
+        std::iter::once(self.state.op_idx).chain(calls).filter_map(|idx| self.flow_at(idx)).collect()
+    }
+    fn flow_at(&self, idx: usize) -> Option<String> {
+        self.marks.iter().filter(|(_, mark)| **mark <= idx).max_by_key(|(_, mark)| **mark).map(|(name, _)| name.clone())
+    }
+    pub fn take_result(&mut self) -> Value {
+        self.state.result.take().unwrap_or(Value::Null)
+    }
+    pub fn take_exit(&mut self) -> Value {
+        self.state.exit.take().unwrap_or(Value::Null)
+    }
+    pub fn yield_value(&mut self, value: Value) {
+        self.state.yielded = Some(value);
+    }
+    pub fn take_yielded(&mut self) -> Option<Value> {
+        self.state.yielded.take()
+    }
+    // the flow starts in a state of its own, its arguments go on its own stack
+    pub fn suspended(&self, flow: &str, args: Vec<Value>) -> Result<Coroutine, String> {
+        self.check_arguments(flow, args.len())?;
+
+        let state = State {
+            op_idx: self.marks[flow],
+            trace: Vec::with_capacity(255),
+            ..State::default()
+        };
+
+        Ok(Coroutine {
+            state,
+            stack: args,
+            frames: vec![BTreeMap::new()],
+            ..Coroutine::default()
+        })
+    }
+    pub fn new_coroutine(&mut self, flow: &str, args: Vec<Value>) -> Result<usize, String> {
+        let coroutine = self.suspended(flow, args)?;
+        let handle = self.state.next_coroutine;
+
+        self.state.coroutines.insert(handle, coroutine);
+        self.state.next_coroutine += 1;
+
+        Ok(handle)
+    }
+    // None once the coroutine has finished or was closed
+    pub fn coroutine(&mut self, handle: usize) -> Result<Option<&mut Coroutine>, String> {
+        if handle >= self.state.next_coroutine {
+            return Err(format!("coroutine {handle} is not started"));
         }
 
-        panic!("segmentation fault, {name_clone} mark name not found")
+        Ok(self.state.coroutines.get_mut(&handle))
+    }
+    // drops the suspended execution, the handle stays finished
+    pub fn close_coroutine(&mut self, handle: usize) -> Result<(), String> {
+        self.coroutine(handle)?;
+        self.state.coroutines.remove(&handle);
+
+        Ok(())
+    }
+    pub fn suspend_loops(&mut self) -> Vec<LoopRecord> {
+        std::mem::take(&mut self.state.loops)
+    }
+    pub fn resume_loops(&mut self, loops: Vec<LoopRecord>) {
+        self.state.loops = loops;
+    }
+    fn current_loop(&self) -> Result<&LoopRecord, String> {
+        match self.state.loops.last() {
+            Some(record) if record.trace_depth < self.state.trace.len() => Ok(record),
+            _ => Err("break and continue are allowed only inside a loop body".to_string()),
+        }
     }
-    pub fn jump_to_program_begin(&mut self) {
-        self.jump_to_mark("#MAIN".to_string());
+    pub fn check_arguments(&self, flow: &str, argc: usize) -> Result<(), String> {
+        match self.arities.get(flow) {
+            None => Err(format!("flow {flow} is not defined")),
+            Some(arity) if *arity != argc => Err(format!("flow {flow} expects {arity} arguments, got {argc}")),
+            Some(_) => Ok(()),
+        }
     }
-    pub fn to_string(&self) -> String {
-        let mut string = String::new();
+    pub fn jump_to_mark(&mut self, name: String) -> Result<(), String> {
+        match self.marks.get(&name) {
+            Some(op_id) => {
+                self.state.op_idx = *op_id;
 
-        let mut i = 0;
+                Ok(())
+            }
+            None => Err(format!("flow {name} is not defined")),
+        }
+    }
+    // the entry flow runs in the outermost frame, its arguments are already on the stack
+    pub fn start_at(&mut self, entry: &str, argc: usize) -> Result<(), String> {
+        self.check_arguments(entry, argc)?;
+
+        self.state.trace.clear();
+        self.state.loops.clear();
+        self.state.result = None;
+        self.state.exit = None;
+        self.state.yielded = None;
+        self.state.coroutines.clear();
+        self.state.next_coroutine = 0;
+        self.state.handlers.clear();
+        self.state.taken.clear();
+        self.jump_to_mark(entry.to_string())
+    }
+}
 
-        for op in &self.ops {
-            string.push_str(format!("{}: {}\n", i, op.to_string()).as_str());
-            i += 1;
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, op) in self.ops.iter().enumerate() {
+            writeln!(f, "{i}: {op}")?;
         }
 
-        string
+        Ok(())
     }
 }
@@ -1,39 +1,81 @@
-#[derive(Clone, Debug)]
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+// strings and collections are shared between clones, mutation copies them only when shared
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     Integer(i64),
     Float(f64),
     Boolean(bool),
+    String(Arc<String>),
+    Array(Arc<Vec<Value>>),
+    Map(Arc<BTreeMap<Key, Value>>),
+    Null,
+}
+
+// floats are not allowed as keys, they have no total order
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Integer(i64),
+    Boolean(bool),
     String(String),
-    Array(Vec<Value>),
+}
+
+impl Key {
+    pub fn from_value(value: &Value) -> Result<Key, String> {
+        match value {
+            Value::Integer(a) => Ok(Key::Integer(*a)),
+            Value::Boolean(a) => Ok(Key::Boolean(*a)),
+            Value::String(a) => Ok(Key::String(a.to_string())),
+            _ => Err(format!("unable to use {} as a map key", value.repr())),
+        }
+    }
+    pub fn to_value(&self) -> Value {
+        match self {
+            Key::Integer(a) => Value::Integer(*a),
+            Key::Boolean(a) => Value::Boolean(*a),
+            Key::String(a) => Value::string(a.clone()),
+        }
+    }
 }
 
 impl Value {
+    pub fn string(value: impl Into<String>) -> Value {
+        Value::String(Arc::new(value.into()))
+    }
+    pub fn array(items: Vec<Value>) -> Value {
+        Value::Array(Arc::new(items))
+    }
+    pub fn map(entries: BTreeMap<Key, Value>) -> Value {
+        Value::Map(Arc::new(entries))
+    }
     pub fn repr(&self) -> String {
         match self {
             Value::Integer(a) => a.to_string(),
             Value::Float(a) => a.to_string(),
             Value::Boolean(a) => a.to_string(),
-            Value::String(a) => a.clone(),
+            Value::String(a) => a.to_string(),
             Value::Array(a) => format!("[{}]", a.iter().map(Value::repr).collect::<Vec<_>>().join(",")),
-            _ => panic!("unable to int({self:?})")
+            Value::Map(a) => format!("{{{}}}", a.iter().map(|(k, v)| format!("{}:{}", k.to_value().repr(), v.repr())).collect::<Vec<_>>().join(",")),
+            Value::Null => "null".to_string(),
         }
     }
-    pub fn to_integer(&self) -> Value {
+    pub fn to_integer(&self) -> Result<Value, String> {
         match self {
-            Value::Integer(a) => Value::Integer(*a),
-            Value::Float(a) => Value::Integer(*a as i64),
-            Value::Boolean(a) => Value::Integer(i64::from(*a)),
-            Value::String(a) => Value::Integer(a.parse::<i64>().unwrap()),
-            _ => panic!("unable to int({self:?})")
+            Value::Integer(a) => Ok(Value::Integer(*a)),
+            Value::Float(a) => Ok(Value::Integer(*a as i64)),
+            Value::Boolean(a) => Ok(Value::Integer(i64::from(*a))),
+            Value::String(a) => a.trim().parse::<i64>().map(Value::Integer).map_err(|_| format!("unable to int({a})")),
+            _ => Err(format!("unable to int({})", self.repr())),
         }
     }
-    pub fn to_float(&self) -> Value {
+    pub fn to_float(&self) -> Result<Value, String> {
         match self {
-            Value::Integer(a) => Value::Float(*a as f64),
-            Value::Float(a) => Value::Float(*a),
-            Value::Boolean(a) => Value::Float(i64::from(*a) as f64),
-            Value::String(a) => Value::Float(a.parse::<f64>().unwrap()),
-            _ => panic!("unable to float({self:?})")
+            Value::Integer(a) => Ok(Value::Float(*a as f64)),
+            Value::Float(a) => Ok(Value::Float(*a)),
+            Value::Boolean(a) => Ok(Value::Float(i64::from(*a) as f64)),
+            Value::String(a) => a.trim().parse::<f64>().map(Value::Float).map_err(|_| format!("unable to float({a})")),
+            _ => Err(format!("unable to float({})", self.repr())),
         }
     }
     pub fn to_bool(&self) -> Value {
@@ -42,89 +84,130 @@ impl Value {
             Value::Float(a) => Value::Boolean(a > &0.0),
             Value::Boolean(a) => Value::Boolean(*a),
             Value::String(a) => Value::Boolean(!a.is_empty()),
-            _ => panic!("unable to bool({self:?})")
+            Value::Array(a) => Value::Boolean(!a.is_empty()),
+            Value::Map(a) => Value::Boolean(!a.is_empty()),
+            Value::Null => Value::Boolean(false),
         }
     }
-    pub fn to_string(&self) -> Value {
+    pub fn to_string(&self) -> Result<Value, String> {
         match self {
-            Value::Integer(a) => Value::String(a.to_string()),
-            Value::Float(a) => Value::String(a.to_string()),
-            Value::Boolean(a) => Value::String(a.to_string()),
-            Value::String(a) => Value::String(a.clone()),
-            _ => panic!("unable to string({self:?})")
+            Value::Integer(a) => Ok(Value::string(a.to_string())),
+            Value::Float(a) => Ok(Value::string(a.to_string())),
+            Value::Boolean(a) => Ok(Value::string(a.to_string())),
+            Value::String(a) => Ok(Value::String(a.clone())),
+            Value::Null => Ok(Value::string("null")),
+            _ => Err(format!("unable to string({})", self.repr())),
         }
     }
-    pub fn add(&self, r: &Self) -> Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+    pub fn add(&self, r: &Self) -> Result<Value, String> {
         match (self, r) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a + b),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
+            (Value::Integer(a), Value::Integer(b)) => match a.checked_add(*b) {
+                Some(result) => Ok(Value::Integer(result)),
+                None => Err(format!("{a} + {b} overflows an integer")),
+            },
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
             (Value::String(a), Value::String(b)) => {
                 let mut combined = String::with_capacity(a.len() + b.len());
 
                 combined.push_str(a);
                 combined.push_str(b);
 
-                Value::String(combined)
+                Ok(Value::string(combined))
             },
-            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(*a || *b),
+            (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(*a || *b)),
             (Value::Array(a), Value::Array(b)) => {
-                let mut new_val = a.clone();
-                new_val.extend(b.clone());
-                Value::Array(new_val)
+                let mut new_val = Vec::with_capacity(a.len() + b.len());
+                new_val.extend_from_slice(a);
+                new_val.extend_from_slice(b);
+                Ok(Value::array(new_val))
             },
-            _ => panic!("unable to {} + {}", self.repr(), r.repr())
+            // keys of the right map win
+            (Value::Map(a), Value::Map(b)) => {
+                let mut new_val = Arc::clone(a);
+                Arc::make_mut(&mut new_val).extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+                Ok(Value::Map(new_val))
+            },
+            _ => Err(format!("unable to {} + {}", self.repr(), r.repr()))
         }
     }
-    pub fn subtract(&self, r: &Self) -> Value {
+    pub fn subtract(&self, r: &Self) -> Result<Value, String> {
         match (self, r) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a - b),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a - b),
-            _ => panic!("unable to {:?} - {:?}", self.repr(), r.repr())
+            (Value::Integer(a), Value::Integer(b)) => match a.checked_sub(*b) {
+                Some(result) => Ok(Value::Integer(result)),
+                None => Err(format!("{a} - {b} overflows an integer")),
+            },
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
+            _ => Err(format!("unable to {} - {}", self.repr(), r.repr()))
         }
     }
-    pub fn multiply(&self, r: &Self) -> Value {
+    pub fn multiply(&self, r: &Self) -> Result<Value, String> {
         match (self, r) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a * b),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a * b),
-            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(*a && *b),
-            _ => panic!("unable to {:?} * {:?}", self.repr(), r.repr())
+            (Value::Integer(a), Value::Integer(b)) => match a.checked_mul(*b) {
+                Some(result) => Ok(Value::Integer(result)),
+                None => Err(format!("{a} * {b} overflows an integer")),
+            },
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
+            (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(*a && *b)),
+            _ => Err(format!("unable to {} * {}", self.repr(), r.repr()))
         }
     }
-    pub fn divide(&self, r: &Self) -> Value {
+    pub fn divide(&self, r: &Self) -> Result<Value, String> {
         match (self, r) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a / b),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a / b),
-            _ => panic!("unable to {:?} / {:?}", self.repr(), r.repr())
+            (Value::Integer(_), Value::Integer(0)) => Err("division by zero".to_string()),
+            (Value::Integer(a), Value::Integer(b)) => match a.checked_div(*b) {
+                Some(result) => Ok(Value::Integer(result)),
+                None => Err(format!("{a} / {b} overflows an integer")),
+            },
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a / b)),
+            _ => Err(format!("unable to {} / {}", self.repr(), r.repr()))
         }
     }
-    pub fn power(&self, r: &Self) -> Value {
+    pub fn power(&self, r: &Self) -> Result<Value, String> {
         match (self, r) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a ^ b),
-            (Value::Float(a), Value::Float(b)) => Value::Float(a.powf(*b)),
-            _ => panic!("unable to {:?} ^ {:?}", self.repr(), r.repr())
+            (Value::Integer(_), Value::Integer(b)) if *b < 0 => Err(format!("integer power {b} is negative, use floats")),
+            (Value::Integer(a), Value::Integer(b)) => match u32::try_from(*b).ok().and_then(|b| a.checked_pow(b)) {
+                Some(result) => Ok(Value::Integer(result)),
+                None => Err(format!("{a} ^ {b} overflows an integer")),
+            },
+            (Value::Float(a), Value::Float(b)) if a.powf(*b).is_nan() => Err(format!("{a} ^ {b} is not a number")),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a.powf(*b))),
+            _ => Err(format!("unable to {} ^ {}", self.repr(), r.repr()))
         }
     }
-    pub fn more(&self, r: &Self) -> Value {
+    pub fn more(&self, r: &Self) -> Result<Value, String> {
         match (self, r) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Boolean(a < b),
-            (Value::Float(a), Value::Float(b)) => Value::Boolean(a < b),
-            _ => panic!("unable to {:?} > {:?}", self.repr(), r.repr())
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a > b)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Boolean(a > b)),
+            _ => Err(format!("unable to {} > {}", self.repr(), r.repr()))
         }
     }
-    pub fn less(&self, r: &Self) -> Value {
+    pub fn less(&self, r: &Self) -> Result<Value, String> {
         match (self, r) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Boolean(a < b),
-            (Value::Float(a), Value::Float(b)) => Value::Boolean(a < b),
-            _ => panic!("unable to {:?} < {:?}", self.repr(), r.repr())
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a < b)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Boolean(a < b)),
+            _ => Err(format!("unable to {} < {}", self.repr(), r.repr()))
         }
     }
-    pub fn eq(&self, r: &Self) -> Value {
+    pub fn eq(&self, r: &Self) -> Result<Value, String> {
         match (self, r) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Boolean(a == b),
-            (Value::Float(a), Value::Float(b)) => Value::Boolean(a == b),
-            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(a == b),
-            (Value::String(a), Value::String(b)) => Value::Boolean(a == b),
-            _ => panic!("unable to {:?} == {:?}", self.repr(), r.repr())
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a == b)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Boolean(a == b)),
+            (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(a == b)),
+            (Value::String(a), Value::String(b)) => Ok(Value::Boolean(a == b)),
+            (Value::Array(a), Value::Array(b)) => Ok(Value::Boolean(a == b)),
+            (Value::Map(a), Value::Map(b)) => Ok(Value::Boolean(a == b)),
+            // anything can be compared with null
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Boolean(self.is_null() && r.is_null())),
+            _ => Err(format!("unable to {} = {}", self.repr(), r.repr()))
+        }
+    }
+    pub fn coalesce(&self, r: &Self) -> Result<Value, String> {
+        match self {
+            Value::Null => Ok(r.clone()),
+            _ => Ok(self.clone()),
         }
     }
-}
\ No newline at end of file
+}
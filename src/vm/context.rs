@@ -0,0 +1,29 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::BTreeMap;
+
+// application state of the host, one value per type
+#[derive(Default)]
+pub struct Context(BTreeMap<TypeId, Box<dyn Any + Send>>);
+
+impl Context {
+    pub fn new() -> Context {
+        Context(BTreeMap::new())
+    }
+    // gives back the previous value of the same type
+    pub fn insert<T: Any + Send>(&mut self, value: T) -> Option<T> {
+        self.0.insert(TypeId::of::<T>(), Box::new(value)).map(|old| *old.downcast::<T>().unwrap())
+    }
+    pub fn get<T: Any + Send>(&self) -> Option<&T> {
+        self.0.get(&TypeId::of::<T>()).map(|value| value.downcast_ref::<T>().unwrap())
+    }
+    pub fn get_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.0.get_mut(&TypeId::of::<T>()).map(|value| value.downcast_mut::<T>().unwrap())
+    }
+    pub fn remove<T: Any + Send>(&mut self) -> Option<T> {
+        self.0.remove(&TypeId::of::<T>()).map(|value| *value.downcast::<T>().unwrap())
+    }
+    // for procedures, a missing value is an error of the script run
+    pub fn require<T: Any + Send>(&mut self) -> Result<&mut T, String> {
+        self.get_mut::<T>().ok_or_else(|| format!("context {} is not provided", type_name::<T>()))
+    }
+}
@@ -0,0 +1,249 @@
+use crate::engine::RuntimeError;
+use crate::program::Value;
+use crate::vm::rng::ScriptRng;
+use crate::vm::tasks::{Scheduler, Tasks};
+use crate::vm::{CancelToken, Context, Limits};
+use rand::Rng;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+// the clock is not read on every op, it costs more than the op itself
+const CLOCK_INTERVAL: u64 = 1024;
+
+// spawned flows write to the same output
+pub type Output = Arc<Mutex<Box<dyn Write + Send>>>;
+// and use the same context, one of them at a time
+pub type SharedContext = Arc<Mutex<Context>>;
+
+// what the VM provides to a running program
+pub struct Env {
+    pub out: Output,
+    pub err: Output,
+    pub rng: ScriptRng,
+    pub context: SharedContext,
+    pub limits: Limits,
+    pub cancel: CancelToken,
+    pub tasks: Arc<Tasks>,
+    ops: u64,
+    // items and bytes of the values the run made, see Limits::max_total_len
+    made: u64,
+    started: Instant,
+    // flows of the executions that resumed the running coroutine
+    outer_depth: usize,
+    // the limit that stopped the run, procedures only see its message
+    exceeded: Option<RuntimeError>,
+    // set by a procedure that has to wait for spawned flows
+    blocked: bool,
+    // kind of the error the running op failed with, set where the error is made like exceeded,
+    // an error made without one is a "runtime" error
+    kind: Option<String>,
+}
+
+impl Env {
+    pub fn new() -> Env {
+        Env {
+            out: Arc::new(Mutex::new(Box::new(io::stdout()))),
+            err: Arc::new(Mutex::new(Box::new(io::stderr()))),
+            rng: ScriptRng::seed_from_u64(rand::rng().next_u64()),
+            context: Arc::new(Mutex::new(Context::new())),
+            limits: Limits::default(),
+            cancel: CancelToken::new(),
+            tasks: Arc::new(Tasks::new(Scheduler::default())),
+            ops: 0,
+            made: 0,
+            started: Instant::now(),
+            outer_depth: 0,
+            exceeded: None,
+            blocked: false,
+            kind: None,
+        }
+    }
+    // for a flow on another thread, it has the limits, outputs and context of the run
+    pub fn worker(&mut self, cancel: CancelToken) -> Env {
+        Env {
+            out: self.out.clone(),
+            err: self.err.clone(),
+            rng: ScriptRng::seed_from_u64(self.rng.next_u64()),
+            context: self.context.clone(),
+            limits: self.limits.clone(),
+            cancel,
+            tasks: self.tasks.clone(),
+            ops: 0,
+            made: 0,
+            started: self.started,
+            outer_depth: 0,
+            exceeded: None,
+            blocked: false,
+            kind: None,
+        }
+    }
+    pub fn write(&mut self, text: &str) -> Result<(), String> {
+        self.out.lock().unwrap().write_all(text.as_bytes()).map_err(|e| format!("unable to write output: {e}"))
+    }
+    pub fn write_error(&mut self, text: &str) -> Result<(), String> {
+        self.err.lock().unwrap().write_all(text.as_bytes()).map_err(|e| format!("unable to write error output: {e}"))
+    }
+    // a host procedure that panicked on another thread does not take the context away
+    pub fn context(&self) -> MutexGuard<'_, Context> {
+        self.context.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.out.lock().unwrap().flush().map_err(|e| format!("unable to write output: {e}"))
+    }
+    pub fn start(&mut self) {
+        self.ops = 0;
+        self.made = 0;
+        self.started = Instant::now();
+        self.outer_depth = 0;
+        self.exceeded = None;
+        self.blocked = false;
+        self.kind = None;
+    }
+    // a restored run keeps the ops and the values it already spent, the clock starts again
+    pub fn restore(&mut self, ops: u64, made: u64) {
+        self.start();
+        self.ops = ops;
+        self.made = made;
+    }
+    // a coroutine counts its flows on top of the flows that resumed it
+    pub fn outer_depth(&self) -> usize {
+        self.outer_depth
+    }
+    pub fn replace_outer_depth(&mut self, depth: usize) -> usize {
+        std::mem::replace(&mut self.outer_depth, depth)
+    }
+    pub fn block(&mut self) {
+        self.blocked = true;
+    }
+    pub fn take_blocked(&mut self) -> bool {
+        std::mem::take(&mut self.blocked)
+    }
+    pub fn fail(&mut self, kind: impl Into<String>, message: String) -> String {
+        self.kind = Some(kind.into());
+
+        message
+    }
+    pub fn take_kind(&mut self) -> Option<String> {
+        self.kind.take()
+    }
+    pub fn is_exceeded(&self) -> bool {
+        self.exceeded.is_some()
+    }
+    pub fn ops(&self) -> u64 {
+        self.ops
+    }
+    pub fn made(&self) -> u64 {
+        self.made
+    }
+    // after every op
+    pub fn check(&mut self, depth: usize, stack_size: usize) -> Result<(), String> {
+        self.ops += 1;
+        // the op did not fail, a kind left by an error a procedure handled itself belongs to nothing
+        self.kind = None;
+
+        if self.cancel.take() {
+            return self.exceed(RuntimeError::Cancelled);
+        }
+
+        if let Some(fuel) = self.limits.fuel && self.ops > fuel {
+            return self.exceed(RuntimeError::OutOfFuel(fuel));
+        }
+        if let Some(limit) = self.limits.stack_size && stack_size > limit {
+            return self.exceed(RuntimeError::StackOverflow(limit));
+        }
+        if let Some(limit) = self.limits.call_depth && self.outer_depth + depth > limit {
+            return self.exceed(RuntimeError::CallDepthExceeded(limit));
+        }
+        if let Some(timeout) = self.limits.timeout && self.ops.is_multiple_of(CLOCK_INTERVAL) && self.started.elapsed() > timeout {
+            return self.exceed(RuntimeError::Timeout(timeout));
+        }
+
+        Ok(())
+    }
+    // between the ops, for procedures that wait
+    pub fn interrupted(&mut self) -> Result<(), String> {
+        if self.cancel.take() {
+            return self.exceed(RuntimeError::Cancelled);
+        }
+        if let Some(timeout) = self.limits.timeout && self.started.elapsed() > timeout {
+            return self.exceed(RuntimeError::Timeout(timeout));
+        }
+
+        Ok(())
+    }
+    // before a procedure makes a value of the length
+    pub fn check_len(&mut self, len: usize) -> Result<(), String> {
+        if let Some(limit) = self.limits.max_value_len && len > limit {
+            return self.exceed(RuntimeError::ValueTooLarge(limit));
+        }
+        if let Some(limit) = self.limits.max_total_len && self.made.saturating_add(len as u64) > limit {
+            return self.exceed(RuntimeError::TotalTooLarge(limit));
+        }
+
+        Ok(())
+    }
+    // the allocator refused a value of the length, the run stops as on the limit
+    pub fn too_large(&mut self, len: usize) -> String {
+        self.exceed(RuntimeError::ValueTooLarge(len)).unwrap_err()
+    }
+    // before a flow is spawned, count is how many the run already has
+    pub fn check_tasks(&mut self, count: usize) -> Result<(), String> {
+        match self.limits.max_tasks {
+            Some(limit) if count >= limit => self.exceed(RuntimeError::TooManyTasks(limit)),
+            _ => Ok(()),
+        }
+    }
+    // the value a procedure returned, base is the first argument it may have grown in place:
+    // a new value counts whole, a grown one only its growth and a shared one nothing
+    pub fn check_value(&mut self, value: &Value, base: Option<Allocation>) -> Result<(), String> {
+        let Some(result) = Allocation::of(value) else {
+            return Ok(());
+        };
+
+        let made = match base {
+            Some(base) if base.address == result.address => result.len.saturating_sub(base.len),
+            _ if result.shared => 0,
+            _ => result.len,
+        };
+
+        self.check_len(made)?;
+        self.made = self.made.saturating_add(made as u64);
+
+        match self.limits.max_value_len {
+            Some(limit) if result.len > limit => self.exceed(RuntimeError::ValueTooLarge(limit)),
+            _ => Ok(()),
+        }
+    }
+    // a limit error keeps its kind, anything else is a failure of the script
+    pub fn error(&mut self, message: String) -> RuntimeError {
+        self.exceeded.take().unwrap_or(RuntimeError::Failed(message))
+    }
+    fn exceed(&mut self, error: RuntimeError) -> Result<(), String> {
+        let message = error.to_string();
+        self.exceeded = Some(error);
+
+        Err(message)
+    }
+}
+
+// where the items of a string, array or map live and how many there are
+#[derive(Clone, Copy)]
+pub struct Allocation {
+    address: *const (),
+    len: usize,
+    shared: bool,
+}
+
+impl Allocation {
+    pub fn of(value: &Value) -> Option<Allocation> {
+        let (address, len, owners) = match value {
+            Value::String(text) => (Arc::as_ptr(text) as *const (), text.len(), Arc::strong_count(text)),
+            Value::Array(items) => (Arc::as_ptr(items) as *const (), items.len(), Arc::strong_count(items)),
+            Value::Map(entries) => (Arc::as_ptr(entries) as *const (), entries.len(), Arc::strong_count(entries)),
+            _ => return None,
+        };
+
+        Some(Allocation { address, len, shared: owners > 1 })
+    }
+}
@@ -0,0 +1,22 @@
+use std::time::Duration;
+
+// none of them is set by default, a script runs until it ends
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Limits {
+    // executed ops
+    pub fuel: Option<u64>,
+    // values on the value stack
+    pub stack_size: Option<usize>,
+    // nested flow calls
+    pub call_depth: Option<usize>,
+    // length of a single array or map, bytes of a single string, it is not a memory limit:
+    // items of nested values and other variables are not counted
+    pub max_value_len: Option<usize>,
+    // items and bytes of all the values the run makes, added up like fuel: what the script
+    // drops is not given back, a copy counts whole and a value grown in place its growth,
+    // every spawned flow has its own count
+    pub max_total_len: Option<u64>,
+    // flows spawned during the run, every one of them may be a thread
+    pub max_tasks: Option<usize>,
+    pub timeout: Option<Duration>,
+}
@@ -1,4 +1,20 @@
+mod cancel;
+mod context;
+mod env;
+mod limits;
 mod vm;
 mod operation;
+mod rng;
+mod runtime;
+mod snapshot;
+mod tasks;
+#[cfg(test)]
+pub(crate) mod testing;
+mod unwind;
 
-pub use crate::vm::vm::{VM, Stack};
+pub use crate::vm::cancel::CancelToken;
+pub use crate::vm::context::Context;
+pub use crate::vm::limits::Limits;
+pub use crate::vm::runtime::Runtime;
+pub use crate::vm::tasks::Scheduler;
+pub use crate::vm::vm::{Progress, Stack, VM};
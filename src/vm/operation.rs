@@ -1,74 +1,214 @@
-use crate::procedure::{get_procedures};
 use crate::program::{Program, Value};
+use crate::vm::env::{Allocation, Env};
+use crate::vm::runtime::Runtime;
 use crate::vm::vm::{Memo, Stack};
 
-pub type Executable = fn(&mut Program, &mut Stack, &mut Memo);
+pub type Executable = fn(&mut Program, &mut Stack, &mut Memo, &mut Env) -> Result<(), String>;
 
-pub fn jmp(pr: &mut Program, _: &mut Stack, _: &mut Memo) {
+pub fn jmp(pr: &mut Program, _: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
     let mark_name = pr.current().unwrap().word.clone().unwrap();
     pr.trace_back();
-    pr.jump_to_mark(mark_name);
+    mem.enter();
+    pr.jump_to_mark(mark_name)
 }
 
-pub fn exec(pr: &mut Program, st: &mut Stack, _: &mut Memo) {
+pub fn exec(pr: &mut Program, st: &mut Stack, mem: &mut Memo, env: &mut Env) -> Result<(), String> {
     let op = pr.current().unwrap();
 
-    let binding = op.word.clone().unwrap();
-    let proc = get_procedures(binding.as_str());
+    let Some(proc) = op.procedure.clone() else {
+        return Err(format!("procedure {} is not linked", op.word.as_ref().unwrap()));
+    };
     let argc = op.count.unwrap();
+    // mutations grow their first argument in place
+    let base = st.values().len().checked_sub(argc).and_then(|i| st.values().get(i)).and_then(Allocation::of);
 
-    proc.execute(argc, st).unwrap();
+    proc.execute(argc, st, &mut Runtime::new(pr, mem, env))?;
+
+    match st.last() {
+        Some(value) => env.check_value(value, base),
+        None => Ok(()),
+    }
 }
 
-pub fn mark(pr: &mut Program, _: &mut Stack, _: &mut Memo) {
-    pr.finish_block();
-    pr.skip(0);
+pub fn mark(pr: &mut Program, _: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
+    if pr.finish_block() {
+        mem.leave();
+    }
+    pr.step_back();
+
+    Ok(())
 }
 
-pub fn push(pr: &mut Program, st: &mut Stack, mem: &mut Memo) {
-    let op = pr.current().unwrap();
+pub fn push(pr: &mut Program, st: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
+    let value = pr.current().unwrap().value.as_ref().unwrap();
+
+    // values are shared, so reading a variable does not copy its content
+    match value {
+        Value::String(name) if name.starts_with('$') => match mem.get(name) {
+            Some(value) => st.push(value.clone()),
+            None => return Err(format!("variable {name} is not defined")),
+        },
+        _ => st.push(value.clone()),
+    }
+
+    Ok(())
+}
+
+pub fn take(pr: &mut Program, st: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
+    let name = pr.current().unwrap().word.as_ref().unwrap();
 
-    let value = op.value.clone().unwrap().clone();
-    let raw_val = value.repr();
+    let Some(value) = mem.take(name) else {
+        return Err(format!("variable {name} is not defined"));
+    };
 
-    if raw_val.starts_with('$') {
-        st.push(mem.get(&raw_val).unwrap().clone());
-    } else {
-        st.push(value);
+    // inside of a try a caught error must not leave the variable empty
+    if pr.is_trying() {
+        pr.take_variable(name.clone(), st.len());
     }
+
+    st.push(value);
+
+    Ok(())
 }
 
-pub fn skip(pr: &mut Program, _: &mut Stack, _: &mut Memo) {
+pub fn skip(pr: &mut Program, _: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
     let skip = pr.current().unwrap().count.unwrap();
 
     pr.skip(skip);
+
+    Ok(())
 }
 
-pub fn cskip(pr: &mut Program, st: &mut Stack, _: &mut Memo) {
-    let operand = st.pop();
+pub fn bskip(pr: &mut Program, _: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
+    let skip = pr.current().unwrap().count.unwrap();
 
-    let condition_result = operand.to_bool().eq(&Value::Boolean(true));
+    pr.skip_back(skip);
+
+    Ok(())
+}
+
+pub fn cskip(pr: &mut Program, st: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
+    let operand = st.pop();
 
-    if let Value::Boolean(true) = condition_result {
+    if let Value::Boolean(true) = operand.to_bool() {
         let skip = pr.current().unwrap().count.unwrap();
 
         pr.skip(skip);
     }
+
+    Ok(())
 }
 
-pub fn var(pr: &mut Program, st: &mut Stack, mem: &mut Memo) {
+pub fn case(pr: &mut Program, st: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
     let op = pr.current().unwrap();
+    let operand = st.pop();
 
-    let var_name = op.word.clone().unwrap();
+    // the value stays on the stack for the next CASE until some arm matches
+    if op.value.as_ref().is_some_and(|pattern| *pattern != operand) {
+        st.push(operand);
+
+        return Ok(());
+    }
+
+    let skip = op.count.unwrap();
+
+    pr.skip(skip);
+
+    Ok(())
+}
 
-    assert!(
-        !mem.contains_key(&var_name),
-        "variable {var_name} already defined"
-    );
+pub fn var(pr: &mut Program, st: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
+    let op = pr.current().unwrap();
+
+    let var_name = op.word.clone().unwrap();
 
     let operand = st.pop();
 
-    mem.insert(var_name, operand);
+    mem.define(var_name, operand)
+}
+
+pub fn set(pr: &mut Program, st: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
+    let var_name = pr.current().unwrap().word.clone().unwrap();
+
+    pr.settle_variable(&var_name);
+
+    mem.assign(var_name, st.pop())
+}
+
+pub fn bind(pr: &mut Program, st: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
+    let var_name = pr.current().unwrap().word.clone().unwrap();
+
+    mem.bind(var_name, st.pop());
+
+    Ok(())
+}
+
+pub fn r#loop(pr: &mut Program, _: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
+    let exit = pr.current().unwrap().count.unwrap();
+
+    pr.enter_loop(exit);
+
+    Ok(())
+}
+
+pub fn endloop(pr: &mut Program, _: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
+    pr.leave_loop();
+
+    Ok(())
+}
+
+pub fn r#break(pr: &mut Program, _: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
+    pr.break_loop()?;
+    mem.truncate(pr.depth() + 1);
+
+    Ok(())
+}
+
+pub fn r#continue(pr: &mut Program, _: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
+    pr.continue_loop()?;
+    mem.truncate(pr.depth() + 1);
+
+    Ok(())
+}
+
+pub fn ret(pr: &mut Program, st: &mut Stack, mem: &mut Memo, _: &mut Env) -> Result<(), String> {
+    if pr.return_flow(st.pop()) {
+        mem.truncate(pr.depth() + 1);
+    }
+
+    Ok(())
+}
+
+pub fn result(pr: &mut Program, st: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
+    st.push(pr.take_result());
+
+    Ok(())
+}
+
+pub fn r#yield(pr: &mut Program, st: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
+    pr.yield_value(st.pop());
+
+    Ok(())
+}
+
+pub fn drop(_: &mut Program, st: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
+    st.pop();
+
+    Ok(())
+}
+
+pub fn r#try(pr: &mut Program, st: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
+    let catch = pr.current().unwrap().count.unwrap();
+
+    pr.enter_try(catch, st.len());
+
+    Ok(())
+}
+
+pub fn endtry(pr: &mut Program, _: &mut Stack, _: &mut Memo, _: &mut Env) -> Result<(), String> {
+    pr.leave_try();
+
+    Ok(())
 }
 
 pub fn get_op_executable(name: &str) -> Executable {
@@ -77,9 +217,24 @@ pub fn get_op_executable(name: &str) -> Executable {
         "EXEC" => exec,
         "MARK" => mark,
         "PUSH" => push,
+        "TAKE" => take,
         "SKIP" => skip,
+        "BSKIP" => bskip,
         "CSKIP" => cskip,
+        "CASE" => case,
         "VAR" => var,
+        "SET" => set,
+        "BIND" => bind,
+        "LOOP" => r#loop,
+        "ENDLOOP" => endloop,
+        "BREAK" => r#break,
+        "CONTINUE" => r#continue,
+        "RET" => ret,
+        "RESULT" => result,
+        "YIELD" => r#yield,
+        "DROP" => drop,
+        "TRY" => r#try,
+        "ENDTRY" => endtry,
         _ => panic!("Unknown variable name"),
     }
 }
@@ -0,0 +1,69 @@
+use rand::TryRng;
+use std::convert::Infallible;
+
+// xoshiro256++, the generator of SmallRng, with a state that can be read back for snapshots
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptRng {
+    s: [u64; 4],
+}
+
+impl ScriptRng {
+    pub fn seed_from_u64(seed: u64) -> ScriptRng {
+        // splitmix64 spreads the seed over the whole state, it is never all zeros
+        let mut seed = seed;
+        let mut s = [0; 4];
+
+        for word in s.iter_mut() {
+            seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *word = z ^ (z >> 31);
+        }
+
+        ScriptRng { s }
+    }
+    pub fn state(&self) -> [u64; 4] {
+        self.s
+    }
+    pub fn from_state(s: [u64; 4]) -> Result<ScriptRng, String> {
+        if s == [0; 4] {
+            return Err(String::from("random generator state is all zeros"));
+        }
+
+        Ok(ScriptRng { s })
+    }
+    fn next(&mut self) -> u64 {
+        let result = self.s[0].wrapping_add(self.s[3]).rotate_left(23).wrapping_add(self.s[0]);
+        let t = self.s[1] << 17;
+
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+
+        result
+    }
+}
+
+impl TryRng for ScriptRng {
+    type Error = Infallible;
+
+    fn try_next_u32(&mut self) -> Result<u32, Infallible> {
+        // the high bits are the better ones
+        Ok((self.next() >> 32) as u32)
+    }
+    fn try_next_u64(&mut self) -> Result<u64, Infallible> {
+        Ok(self.next())
+    }
+    fn try_fill_bytes(&mut self, dst: &mut [u8]) -> Result<(), Infallible> {
+        for chunk in dst.chunks_mut(8) {
+            let bytes = self.next().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+
+        Ok(())
+    }
+}
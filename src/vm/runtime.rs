@@ -0,0 +1,210 @@
+use crate::program::{Coroutine, Program, Value};
+use crate::vm::env::Env;
+use crate::vm::rng::ScriptRng;
+use crate::vm::Context;
+use crate::vm::operation::get_op_executable;
+use crate::vm::tasks::check_op;
+use crate::vm::unwind::unwind;
+use crate::vm::vm::{Memo, Stack};
+use std::sync::MutexGuard;
+
+// what a procedure can reach of the running program
+pub struct Runtime<'a> {
+    program: &'a mut Program,
+    memo: &'a mut Memo,
+    env: &'a mut Env,
+}
+
+impl<'a> Runtime<'a> {
+    pub(crate) fn new(program: &'a mut Program, memo: &'a mut Memo, env: &'a mut Env) -> Self {
+        Runtime { program, memo, env }
+    }
+    // the error of the procedure has the kind, a catch sees it instead of "runtime"
+    pub fn fail(&mut self, kind: impl Into<String>, message: String) -> String {
+        self.env.fail(kind, message)
+    }
+    pub(crate) fn rng(&mut self) -> &mut ScriptRng {
+        &mut self.env.rng
+    }
+    pub fn write(&mut self, text: &str) -> Result<(), String> {
+        self.env.write(text)
+    }
+    pub fn write_error(&mut self, text: &str) -> Result<(), String> {
+        self.env.write_error(text)
+    }
+    // the context is locked while the guard lives, spawned flows wait for it
+    pub fn context(&mut self) -> MutexGuard<'_, Context> {
+        self.env.context()
+    }
+    // procedures call it before they make a long value, hosts too
+    pub fn check_len(&mut self, len: usize) -> Result<(), String> {
+        self.env.check_len(len)
+    }
+    // when the memory for a checked length can't be reserved
+    pub fn too_large(&mut self, len: usize) -> String {
+        self.env.too_large(len)
+    }
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.memo.get(name)
+    }
+    // runs the flow like CALL does and comes back to the procedure with its value
+    pub fn call(&mut self, flow: &str, args: Vec<Value>) -> Result<Value, String> {
+        self.program.check_arguments(flow, args.len())?;
+
+        let mut stack = Stack::new();
+
+        for arg in args {
+            stack.push(arg);
+        }
+
+        let depth = self.program.depth();
+        // break and continue of the callback must not reach loops of the caller
+        let loops = self.program.suspend_loops();
+
+        self.program.trace_back();
+        self.memo.enter();
+        self.program.jump_to_mark(flow.to_string())?;
+
+        let finished = self.run_until(depth, &mut stack);
+
+        self.program.resume_loops(loops);
+        finished?;
+
+        Ok(self.program.take_result())
+    }
+    // the flow runs only when it is resumed, the handle is valid until the run ends
+    pub(crate) fn start_coroutine(&mut self, flow: &str, args: Vec<Value>) -> Result<usize, String> {
+        self.program.new_coroutine(flow, args)
+    }
+    // runs the coroutine to its next yield, false when its flow has finished
+    pub(crate) fn resume_coroutine(&mut self, handle: usize) -> Result<bool, String> {
+        let Some(slot) = self.program.coroutine(handle)? else {
+            return Ok(false);
+        };
+
+        let coroutine = std::mem::take(slot);
+
+        let mut stack = Stack::from_values(coroutine.stack);
+        let mut memo = Memo::from_frames(coroutine.frames);
+
+        let outer = self.env.replace_outer_depth(self.env.outer_depth() + self.program.depth() + 1);
+        let caller = self.program.replace_state(coroutine.state);
+        let produced = run_coroutine(self.program, &mut stack, &mut memo, self.env);
+        let state = self.program.replace_state(caller);
+        self.env.replace_outer_depth(outer);
+
+        match produced {
+            Ok(Some(value)) => {
+                // the slot still holds the placeholder left by take
+                if let Some(slot) = self.program.coroutine(handle)? {
+                    *slot = Coroutine {
+                        state,
+                        stack: stack.into_values(),
+                        frames: memo.into_frames(),
+                        current: Some(value),
+                    };
+                }
+
+                Ok(true)
+            }
+            // a finished or failed coroutine is dropped, its handle stays finished
+            finished => {
+                self.program.close_coroutine(handle)?;
+
+                finished.map(|_| false)
+            }
+        }
+    }
+    // null once the coroutine has finished
+    pub(crate) fn coroutine_value(&mut self, handle: usize) -> Result<Value, String> {
+        Ok(self.program.coroutine(handle)?.and_then(|coroutine| coroutine.current.clone()).unwrap_or(Value::Null))
+    }
+    pub(crate) fn close_coroutine(&mut self, handle: usize) -> Result<(), String> {
+        self.program.close_coroutine(handle)
+    }
+    pub(crate) fn spawn(&mut self, flow: &str, args: Vec<Value>) -> Result<usize, String> {
+        let tasks = self.env.tasks.clone();
+
+        tasks.spawn(self.program, self.env, flow, args)
+    }
+    // None when the procedure has to be tried again after the spawned flows run
+    pub(crate) fn join(&mut self, handle: usize) -> Result<Option<Value>, String> {
+        let tasks = self.env.tasks.clone();
+
+        tasks.join(handle, self.env)
+    }
+    pub(crate) fn channel(&mut self) -> usize {
+        self.env.tasks.channel()
+    }
+    pub(crate) fn send(&mut self, channel: usize, value: Value) -> Result<(), String> {
+        self.env.tasks.send(channel, value)
+    }
+    pub(crate) fn recv(&mut self, channel: usize) -> Result<Option<Value>, String> {
+        let tasks = self.env.tasks.clone();
+
+        tasks.recv(channel, self.env)
+    }
+    fn run_until(&mut self, depth: usize, stack: &mut Stack) -> Result<(), String> {
+        loop {
+            self.program.next();
+
+            if self.program.is_end() && self.program.finish_block() {
+                self.memo.leave();
+
+                if self.program.depth() == depth {
+                    self.program.step_back();
+
+                    return Ok(());
+                }
+            }
+
+            let Some(op) = self.program.current() else {
+                return Err(String::from("program ended inside of a callback"));
+            };
+
+            let executed = get_op_executable(op.name)(self.program, stack, self.memo, self.env);
+
+            // only a try inside of the callback catches here, others get the error from the procedure
+            if let Err(e) = executed.and_then(|_| check_op(self.program, self.env, stack.len())) {
+                unwind(self.program, stack, self.memo, self.env, e, depth + 1)?;
+
+                continue;
+            }
+
+            if self.program.take_yielded().is_some() {
+                return Err(String::from("a callback is not able to yield"));
+            }
+
+            if self.program.depth() == depth {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// the coroutine continues in its own state until it yields or its flow ends
+fn run_coroutine(pr: &mut Program, stack: &mut Stack, memo: &mut Memo, env: &mut Env) -> Result<Option<Value>, String> {
+    loop {
+        pr.next();
+
+        if pr.is_end() && pr.finish_block() {
+            memo.leave();
+        }
+
+        let Some(op) = pr.current() else {
+            return Ok(None);
+        };
+
+        let executed = get_op_executable(op.name)(pr, stack, memo, env);
+
+        if let Err(e) = executed.and_then(|_| check_op(pr, env, stack.len())) {
+            unwind(pr, stack, memo, env, e, 0)?;
+
+            continue;
+        }
+
+        if let Some(value) = pr.take_yielded() {
+            return Ok(Some(value));
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use crate::program::{Coroutine, Handler, Key, LoopRecord, Program, State, Taken, Value};
+use std::collections::BTreeMap;
+
+const MAGIC: &[u8; 6] = b"MPSNAP";
+// bumped whenever the layout below changes, older snapshots are refused
+const VERSION: u16 = 6;
+
+// a paused run, everything that is needed to continue it in another process
+pub struct Snapshot {
+    pub hash: u64,
+    pub state: State,
+    pub stack: Vec<Value>,
+    pub frames: Vec<BTreeMap<String, Value>>,
+    pub rng: [u64; 4],
+    pub ops: u64,
+    pub made: u64,
+}
+
+impl Snapshot {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::new());
+
+        w.0.extend_from_slice(MAGIC);
+        w.0.extend_from_slice(&VERSION.to_le_bytes());
+        w.u64(self.hash);
+
+        w.state(&self.state);
+        w.values(&self.stack);
+        w.frames(&self.frames);
+
+        for word in self.rng {
+            w.u64(word);
+        }
+        w.u64(self.ops);
+        w.u64(self.made);
+
+        w.0
+    }
+    pub fn decode(bytes: &[u8]) -> Result<Snapshot, String> {
+        let mut r = Reader(bytes);
+
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(String::from("not a snapshot"));
+        }
+
+        let version = u16::from_le_bytes(r.take(2)?.try_into().unwrap());
+        if version != VERSION {
+            return Err(format!("snapshot version {version} is not supported, expected {VERSION}"));
+        }
+
+        let hash = r.u64()?;
+
+        let state = r.state()?;
+        let stack = r.values()?;
+        let frames = r.frames()?;
+
+        let rng = [r.u64()?, r.u64()?, r.u64()?, r.u64()?];
+        let ops = r.u64()?;
+        let made = r.u64()?;
+
+        if !r.0.is_empty() {
+            return Err(String::from("snapshot has trailing bytes"));
+        }
+
+        Ok(Snapshot {
+            hash,
+            state,
+            stack,
+            frames,
+            rng,
+            ops,
+            made,
+        })
+    }
+}
+
+// fnv-1a over the encoded ops, stable between builds unlike the std hasher
+pub fn program_hash(pr: &Program) -> u64 {
+    let mut w = Writer(Vec::new());
+
+    for op in pr.operations() {
+        w.string(op.name);
+        match &op.word {
+            Some(word) => {
+                w.0.push(1);
+                w.string(word);
+            }
+            None => w.0.push(0),
+        }
+        w.option(op.value.as_ref());
+        match op.count {
+            Some(count) => {
+                w.0.push(1);
+                w.u64(count as u64);
+            }
+            None => w.0.push(0),
+        }
+    }
+
+    w.0.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// positions of the state and of its coroutines point into a program of the length
+pub fn fits(state: &State, len: usize) -> bool {
+    state.op_idx <= len
+        && state.trace.iter().all(|idx| *idx <= len)
+        && state.loops.iter().all(|record| record.exit <= len)
+        && state.handlers.iter().all(|handler| handler.catch <= len)
+        && state.coroutines.iter().all(|(handle, coroutine)| *handle < state.next_coroutine && !coroutine.frames.is_empty() && fits(&coroutine.state, len))
+}
+
+const NULL: u8 = 0;
+const INTEGER: u8 = 1;
+const FLOAT: u8 = 2;
+const BOOLEAN: u8 = 3;
+const STRING: u8 = 4;
+const ARRAY: u8 = 5;
+const MAP: u8 = 6;
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn string(&mut self, text: &str) {
+        self.u64(text.len() as u64);
+        self.0.extend_from_slice(text.as_bytes());
+    }
+    fn state(&mut self, state: &State) {
+        self.u64(state.op_idx as u64);
+        self.u64(state.trace.len() as u64);
+        for idx in &state.trace {
+            self.u64(*idx as u64);
+        }
+        self.u64(state.loops.len() as u64);
+        for record in &state.loops {
+            self.u64(record.trace_depth as u64);
+            self.u64(record.exit as u64);
+        }
+        self.option(state.result.as_ref());
+        self.option(state.exit.as_ref());
+        self.option(state.yielded.as_ref());
+        self.u64(state.handlers.len() as u64);
+        for handler in &state.handlers {
+            self.u64(handler.catch as u64);
+            self.u64(handler.depth as u64);
+            self.u64(handler.loops as u64);
+            self.u64(handler.stack as u64);
+        }
+        self.u64(state.taken.len() as u64);
+        for taken in &state.taken {
+            self.string(&taken.name);
+            self.u64(taken.stack as u64);
+        }
+
+        // suspended coroutines are states of their own
+        self.u64(state.next_coroutine as u64);
+        self.u64(state.coroutines.len() as u64);
+        for (handle, coroutine) in &state.coroutines {
+            self.u64(*handle as u64);
+            self.state(&coroutine.state);
+            self.values(&coroutine.stack);
+            self.frames(&coroutine.frames);
+            self.option(coroutine.current.as_ref());
+        }
+    }
+    fn values(&mut self, values: &[Value]) {
+        self.u64(values.len() as u64);
+        for value in values {
+            self.value(value);
+        }
+    }
+    fn frames(&mut self, frames: &[BTreeMap<String, Value>]) {
+        self.u64(frames.len() as u64);
+        for frame in frames {
+            self.u64(frame.len() as u64);
+            for (name, value) in frame {
+                self.string(name);
+                self.value(value);
+            }
+        }
+    }
+    fn option(&mut self, value: Option<&Value>) {
+        match value {
+            Some(value) => {
+                self.0.push(1);
+                self.value(value);
+            }
+            None => self.0.push(0),
+        }
+    }
+    fn key(&mut self, key: &Key) {
+        match key {
+            Key::Integer(a) => {
+                self.0.push(INTEGER);
+                self.u64(*a as u64);
+            }
+            Key::Boolean(a) => self.0.extend_from_slice(&[BOOLEAN, *a as u8]),
+            Key::String(a) => {
+                self.0.push(STRING);
+                self.string(a);
+            }
+        }
+    }
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.0.push(NULL),
+            Value::Integer(a) => {
+                self.0.push(INTEGER);
+                self.u64(*a as u64);
+            }
+            Value::Float(a) => {
+                self.0.push(FLOAT);
+                self.u64(a.to_bits());
+            }
+            Value::Boolean(a) => self.0.extend_from_slice(&[BOOLEAN, *a as u8]),
+            Value::String(a) => {
+                self.0.push(STRING);
+                self.string(a);
+            }
+            Value::Array(items) => {
+                self.0.push(ARRAY);
+                self.u64(items.len() as u64);
+                for item in items.iter() {
+                    self.value(item);
+                }
+            }
+            Value::Map(entries) => {
+                self.0.push(MAP);
+                self.u64(entries.len() as u64);
+                for (key, value) in entries.iter() {
+                    self.key(key);
+                    self.value(value);
+                }
+            }
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], String> {
+        if size > self.0.len() {
+            return Err(String::from("snapshot is truncated"));
+        }
+
+        let (taken, rest) = self.0.split_at(size);
+        self.0 = rest;
+
+        Ok(taken)
+    }
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn usize(&mut self) -> Result<usize, String> {
+        usize::try_from(self.u64()?).map_err(|_| String::from("snapshot index does not fit"))
+    }
+    // every item takes at least a byte, a longer count is a broken snapshot, not an allocation
+    fn len(&mut self) -> Result<usize, String> {
+        let len = self.usize()?;
+
+        if len > self.0.len() {
+            return Err(String::from("snapshot is truncated"));
+        }
+
+        Ok(len)
+    }
+    fn bool(&mut self) -> Result<bool, String> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(format!("invalid boolean {byte} in snapshot")),
+        }
+    }
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.len()?;
+
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| String::from("invalid string in snapshot"))
+    }
+    fn state(&mut self) -> Result<State, String> {
+        let op_idx = self.usize()?;
+        let trace = (0..self.len()?).map(|_| self.usize()).collect::<Result<_, _>>()?;
+        let loops = (0..self.len()?)
+            .map(|_| Ok(LoopRecord { trace_depth: self.usize()?, exit: self.usize()? }))
+            .collect::<Result<_, String>>()?;
+        let result = self.option()?;
+        let exit = self.option()?;
+        let yielded = self.option()?;
+        let handlers = (0..self.len()?)
+            .map(|_| {
+                Ok(Handler {
+                    catch: self.usize()?,
+                    depth: self.usize()?,
+                    loops: self.usize()?,
+                    stack: self.usize()?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        let taken = (0..self.len()?)
+            .map(|_| Ok(Taken { name: self.string()?, stack: self.usize()? }))
+            .collect::<Result<_, String>>()?;
+        let next_coroutine = self.usize()?;
+        let coroutines = (0..self.len()?)
+            .map(|_| {
+                let handle = self.usize()?;
+                let coroutine = Coroutine {
+                    state: self.state()?,
+                    stack: self.values()?,
+                    frames: self.frames()?,
+                    current: self.option()?,
+                };
+
+                Ok((handle, coroutine))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(State { op_idx, trace, loops, result, exit, yielded, coroutines, next_coroutine, handlers, taken })
+    }
+    fn values(&mut self) -> Result<Vec<Value>, String> {
+        (0..self.len()?).map(|_| self.value()).collect()
+    }
+    fn frames(&mut self) -> Result<Vec<BTreeMap<String, Value>>, String> {
+        (0..self.len()?)
+            .map(|_| (0..self.len()?).map(|_| Ok((self.string()?, self.value()?))).collect())
+            .collect()
+    }
+    fn option(&mut self) -> Result<Option<Value>, String> {
+        match self.bool()? {
+            true => Ok(Some(self.value()?)),
+            false => Ok(None),
+        }
+    }
+    fn key(&mut self) -> Result<Key, String> {
+        match self.byte()? {
+            INTEGER => Ok(Key::Integer(self.u64()? as i64)),
+            BOOLEAN => Ok(Key::Boolean(self.bool()?)),
+            STRING => Ok(Key::String(self.string()?)),
+            tag => Err(format!("invalid key tag {tag} in snapshot")),
+        }
+    }
+    fn value(&mut self) -> Result<Value, String> {
+        match self.byte()? {
+            NULL => Ok(Value::Null),
+            INTEGER => Ok(Value::Integer(self.u64()? as i64)),
+            FLOAT => Ok(Value::Float(f64::from_bits(self.u64()?))),
+            BOOLEAN => Ok(Value::Boolean(self.bool()?)),
+            STRING => Ok(Value::string(self.string()?)),
+            ARRAY => {
+                let items = (0..self.len()?).map(|_| self.value()).collect::<Result<_, _>>()?;
+                Ok(Value::array(items))
+            }
+            MAP => {
+                let entries = (0..self.len()?).map(|_| Ok((self.key()?, self.value()?))).collect::<Result<_, String>>()?;
+                Ok(Value::map(entries))
+            }
+            tag => Err(format!("invalid value tag {tag} in snapshot")),
+        }
+    }
+}
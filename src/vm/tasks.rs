@@ -0,0 +1,385 @@
+use crate::program::{Coroutine, Program, Value};
+use crate::vm::env::Env;
+use crate::vm::operation::get_op_executable;
+use crate::vm::unwind::unwind;
+use crate::vm::vm::{Memo, Stack};
+use crate::vm::CancelToken;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// ops a spawned flow runs before the deterministic scheduler moves to the next one
+const SLICE: u64 = 64;
+// how often a waiting thread looks at cancellation and the timeout
+const WAIT_INTERVAL: Duration = Duration::from_millis(10);
+const BLOCKED: &str = "flows are blocked waiting for each other";
+
+// how spawned flows run
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Scheduler {
+    // every spawned flow gets a thread
+    #[default]
+    Threads,
+    // spawned flows take turns on the thread of the run in the order they were spawned,
+    // every run of a script does the same, it is meant for tests
+    Deterministic,
+}
+
+// values put in at one end and taken in order at the other
+struct Mailbox<T> {
+    queue: Mutex<Queue<T>>,
+    ready: Condvar,
+}
+
+struct Queue<T> {
+    items: VecDeque<T>,
+    // threads waiting for an item that no put has woken yet
+    waiters: usize,
+}
+
+impl<T> Mailbox<T> {
+    fn new() -> Self {
+        Mailbox {
+            queue: Mutex::new(Queue { items: VecDeque::new(), waiters: 0 }),
+            ready: Condvar::new(),
+        }
+    }
+    // a waiting thread counts as running again from the moment it has an item
+    fn put(&self, item: T, running: &Mutex<usize>) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.items.push_back(item);
+
+        if queue.waiters > 0 {
+            queue.waiters -= 1;
+            *running.lock().unwrap() += 1;
+        }
+
+        self.ready.notify_one();
+    }
+    fn try_take(&self) -> Option<T> {
+        self.queue.lock().unwrap().items.pop_front()
+    }
+    // blocks the thread, a cancelled or timed out run stops waiting, and so does a run whose
+    // flows all wait, none of them is able to put an item anymore
+    fn wait(&self, env: &mut Env, running: &Mutex<usize>) -> Result<T, String> {
+        let mut queue = self.queue.lock().unwrap();
+
+        if let Some(item) = queue.items.pop_front() {
+            return Ok(item);
+        }
+
+        queue.waiters += 1;
+        *running.lock().unwrap() -= 1;
+
+        loop {
+            if let Some(item) = queue.items.pop_front() {
+                return Ok(item);
+            }
+
+            let stopped = match *running.lock().unwrap() {
+                0 => Err(String::from(BLOCKED)),
+                _ => env.interrupted(),
+            };
+
+            if let Err(e) = stopped {
+                queue.waiters -= 1;
+                *running.lock().unwrap() += 1;
+
+                return Err(e);
+            }
+
+            queue = self.ready.wait_timeout(queue, WAIT_INTERVAL).unwrap().0;
+        }
+    }
+}
+
+// a failed flow gives its error with the kind a catch would have seen
+type Finished = Result<Value, (String, Option<String>)>;
+
+struct Task {
+    flow: String,
+    result: Arc<Mailbox<Finished>>,
+    // stops the thread of the flow when the run ends
+    cancel: CancelToken,
+    // the deterministic scheduler keeps the flow here between its turns
+    suspended: Option<Coroutine>,
+    joined: bool,
+}
+
+// what run_slice stopped at
+enum Slice {
+    Finished(Value),
+    Blocked,
+    Paused,
+}
+
+// what a handle of the script names, tasks and channels are numbered together,
+// so a channel is not joined and a task is not received from
+enum Handle {
+    Task(Box<Task>),
+    Channel(Arc<Mailbox<Value>>),
+}
+
+// spawned flows and channels of a run, shared with the threads of the run
+pub struct Tasks {
+    scheduler: Scheduler,
+    handles: Mutex<Vec<Handle>>,
+    spawned: Mutex<usize>,
+    // threads of the run that are not waiting, the flow of the run included
+    running: Mutex<usize>,
+}
+
+impl Tasks {
+    pub fn new(scheduler: Scheduler) -> Tasks {
+        Tasks {
+            scheduler,
+            handles: Mutex::new(vec![]),
+            spawned: Mutex::new(0),
+            running: Mutex::new(1),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.handles.lock().unwrap().is_empty()
+    }
+    pub fn spawn(&self, pr: &Program, env: &mut Env, flow: &str, args: Vec<Value>) -> Result<usize, String> {
+        env.check_tasks(*self.spawned.lock().unwrap())?;
+
+        let coroutine = pr.suspended(flow, args)?;
+        let result = Arc::new(Mailbox::new());
+        let cancel = CancelToken::new();
+
+        let suspended = match self.scheduler {
+            Scheduler::Deterministic => Some(coroutine),
+            Scheduler::Threads => {
+                let mut fork = pr.fork();
+                let mut worker = env.worker(cancel.clone());
+                let finished = result.clone();
+                let tasks = worker.tasks.clone();
+
+                *self.running.lock().unwrap() += 1;
+                fork.set_state(coroutine.state);
+                thread::spawn(move || {
+                    // a panic must still reach the join, or it would wait forever
+                    let value = panic::catch_unwind(AssertUnwindSafe(|| {
+                        let mut stack = Stack::from_values(coroutine.stack);
+                        let mut memo = Memo::from_frames(coroutine.frames);
+
+                        match run_slice(&mut fork, &mut stack, &mut memo, &mut worker, None) {
+                            Ok(Slice::Finished(value)) => Ok(value),
+                            Ok(_) => unreachable!("a thread runs its flow to the end"),
+                            Err(e) => Err((e, worker.take_kind())),
+                        }
+                    }));
+
+                    finished.put(value.unwrap_or_else(|payload| Err((panic_message(payload), None))), &tasks.running);
+                    *tasks.running.lock().unwrap() -= 1;
+                });
+
+                None
+            }
+        };
+
+        *self.spawned.lock().unwrap() += 1;
+
+        let mut handles = self.handles.lock().unwrap();
+        handles.push(Handle::Task(Box::new(Task {
+            flow: flow.to_string(),
+            result,
+            cancel,
+            suspended,
+            joined: false,
+        })));
+
+        Ok(handles.len() - 1)
+    }
+    // None when the deterministic scheduler has to run other flows first
+    pub fn join(&self, handle: usize, env: &mut Env) -> Result<Option<Value>, String> {
+        let (flow, result) = match self.handles.lock().unwrap().get(handle) {
+            Some(Handle::Task(task)) if task.joined => return Err(format!("task {handle} is already joined")),
+            Some(Handle::Task(task)) => (task.flow.clone(), task.result.clone()),
+            _ => return Err(format!("task {handle} is not spawned")),
+        };
+
+        let Some(finished) = self.take(&result, env)? else {
+            return Ok(None);
+        };
+
+        if let Handle::Task(task) = &mut self.handles.lock().unwrap()[handle] {
+            task.joined = true;
+        }
+
+        finished.map(Some).map_err(|(e, kind)| {
+            let message = format!("flow {flow} failed: {e}");
+
+            match kind {
+                Some(kind) => env.fail(kind, message),
+                None => message,
+            }
+        })
+    }
+    pub fn channel(&self) -> usize {
+        let mut handles = self.handles.lock().unwrap();
+        handles.push(Handle::Channel(Arc::new(Mailbox::new())));
+
+        handles.len() - 1
+    }
+    pub fn send(&self, channel: usize, value: Value) -> Result<(), String> {
+        self.mailbox(channel)?.put(value, &self.running);
+
+        Ok(())
+    }
+    pub fn recv(&self, channel: usize, env: &mut Env) -> Result<Option<Value>, String> {
+        let mailbox = self.mailbox(channel)?;
+
+        self.take(&mailbox, env)
+    }
+    // flows nobody joined do not outlive the run
+    pub fn stop(&self) {
+        for handle in self.handles.lock().unwrap().iter_mut() {
+            if let Handle::Task(task) = handle {
+                task.cancel.cancel();
+                task.suspended = None;
+            }
+        }
+    }
+    // one turn of every suspended flow, false when none of them got any further
+    pub fn run_round(&self, pr: &mut Program, env: &mut Env) -> Result<bool, String> {
+        let mut progressed = false;
+        let mut handle = 0;
+
+        loop {
+            let coroutine = match self.handles.lock().unwrap().get_mut(handle) {
+                None => break,
+                Some(Handle::Task(task)) => task.suspended.take(),
+                Some(Handle::Channel(_)) => None,
+            };
+            handle += 1;
+
+            let Some(coroutine) = coroutine else {
+                continue;
+            };
+
+            let mut stack = Stack::from_values(coroutine.stack);
+            let mut memo = Memo::from_frames(coroutine.frames);
+            let ops = env.ops();
+
+            let outer = env.replace_outer_depth(0);
+            let caller = pr.replace_state(coroutine.state);
+            let slice = run_slice(pr, &mut stack, &mut memo, env, Some(SLICE));
+            let state = pr.replace_state(caller);
+            env.replace_outer_depth(outer);
+
+            progressed |= env.ops() > ops;
+
+            let mut handles = self.handles.lock().unwrap();
+            let Handle::Task(task) = &mut handles[handle - 1] else {
+                unreachable!("only a task is suspended");
+            };
+
+            match slice {
+                Ok(Slice::Finished(value)) => task.result.put(Ok(value), &self.running),
+                Ok(_) => {
+                    task.suspended = Some(Coroutine {
+                        state,
+                        stack: stack.into_values(),
+                        frames: memo.into_frames(),
+                        ..Coroutine::default()
+                    })
+                }
+                // a limit stops the whole run, not only the flow that reached it
+                Err(e) if env.is_exceeded() => return Err(e),
+                Err(e) => task.result.put(Err((e, env.take_kind())), &self.running),
+            }
+        }
+
+        Ok(progressed)
+    }
+    fn mailbox(&self, channel: usize) -> Result<Arc<Mailbox<Value>>, String> {
+        match self.handles.lock().unwrap().get(channel) {
+            Some(Handle::Channel(mailbox)) => Ok(mailbox.clone()),
+            _ => Err(format!("channel {channel} is not open")),
+        }
+    }
+    fn take<T>(&self, mailbox: &Mailbox<T>, env: &mut Env) -> Result<Option<T>, String> {
+        if let Some(item) = mailbox.try_take() {
+            return Ok(Some(item));
+        }
+
+        match self.scheduler {
+            Scheduler::Threads => mailbox.wait(env, &self.running).map(Some),
+            Scheduler::Deterministic => {
+                env.block();
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast::<&str>().map(|message| message.to_string()).unwrap_or_default(),
+    };
+
+    format!("the flow panicked: {message}")
+}
+
+// after every op of a flow that is not spawned, a blocked op lets the spawned flows run and is tried again
+pub fn check_op(pr: &mut Program, env: &mut Env, stack_size: usize) -> Result<(), String> {
+    if !env.take_blocked() {
+        return env.check(pr.depth(), stack_size);
+    }
+
+    pr.step_back();
+
+    let tasks = env.tasks.clone();
+
+    match tasks.run_round(pr, env)? {
+        true => Ok(()),
+        false => Err(String::from(BLOCKED)),
+    }
+}
+
+// the flow continues in the state of the program until it ends, blocks or spends the budget
+fn run_slice(pr: &mut Program, stack: &mut Stack, memo: &mut Memo, env: &mut Env, budget: Option<u64>) -> Result<Slice, String> {
+    let until = budget.map(|ops| env.ops() + ops);
+
+    loop {
+        if until.is_some_and(|until| env.ops() >= until) {
+            return Ok(Slice::Paused);
+        }
+
+        pr.next();
+
+        if pr.is_end() && pr.finish_block() {
+            memo.leave();
+        }
+
+        let Some(op) = pr.current() else {
+            return Ok(Slice::Finished(pr.take_exit()));
+        };
+
+        if let Err(e) = get_op_executable(op.name)(pr, stack, memo, env) {
+            unwind(pr, stack, memo, env, e, 0)?;
+
+            continue;
+        }
+
+        // the op runs again on the next turn
+        if env.take_blocked() {
+            pr.step_back();
+
+            return Ok(Slice::Blocked);
+        }
+
+        env.check(pr.depth(), stack.len())?;
+
+        if pr.take_yielded().is_some() {
+            return Err(String::from("a spawned flow is not able to yield"));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::compiler::Compiler;
+use crate::engine::{CompiledScript, Engine};
+use crate::lexer::TokenStream;
+use crate::parser::Parser;
+use crate::procedure::ProcedureRegistry;
+use crate::program::{Program, Value};
+use crate::vm::vm::{Memo, VM};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+// output the tests read back after the run
+#[derive(Clone, Default)]
+pub(crate) struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buffer {
+    pub(crate) fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+pub(crate) fn compile(source: &str) -> Result<Program, String> {
+    let procedures = Arc::new(ProcedureRegistry::new());
+    let stream = TokenStream::new(source.to_string()).map_err(|e| e.message)?;
+    let tree = Parser::with_procedures(stream, procedures.clone()).parse_program()?;
+    let mut compiler = Compiler::with_procedures(procedures);
+    compiler.compile(tree)?;
+    compiler.link()?;
+
+    Ok(compiler.program)
+}
+
+// the variables #MAIN left, or the message of the error
+pub(crate) fn run_on(vm: &mut VM, source: &str) -> Result<Memo, String> {
+    let mut program = compile(source)?;
+
+    vm.run(&mut program, "#MAIN", vec![]).map_err(|e| e.message())?;
+
+    Ok(vm.take_memo())
+}
+
+pub(crate) fn run(source: &str) -> Result<Memo, String> {
+    let mut vm = VM::new();
+    vm.set_output(Box::new(io::sink()));
+
+    run_on(&mut vm, source)
+}
+
+pub(crate) fn integer(memo: &Memo, name: &str) -> i64 {
+    let Some(Value::Integer(value)) = memo.get(name) else {
+        panic!("{name} is not an integer");
+    };
+
+    *value
+}
+
+// through the Engine, like a host runs it
+pub(crate) fn compile_script(source: &str) -> CompiledScript {
+    let mut script = Engine::new().compile(source).unwrap();
+    script.set_output(Box::new(io::sink()));
+
+    script
+}
@@ -0,0 +1,37 @@
+use crate::program::{Key, Program, Value};
+use crate::vm::env::Env;
+use crate::vm::vm::{Memo, Stack};
+use std::collections::BTreeMap;
+
+// the failed op continues at the catch of the innermost try of a flow at the depth or deeper,
+// the catch gets the error as a value, limits stop the run and are never caught
+pub fn unwind(pr: &mut Program, stack: &mut Stack, memo: &mut Memo, env: &mut Env, error: String, depth: usize) -> Result<(), String> {
+    if env.is_exceeded() || !pr.is_trying_from(depth) {
+        return Err(error);
+    }
+
+    let kind = env.take_kind().unwrap_or_else(|| String::from("runtime"));
+    let backtrace = pr.backtrace().into_iter().map(Value::string).collect();
+
+    let mut entries = BTreeMap::new();
+    entries.insert(Key::String(String::from("message")), Value::string(error));
+    entries.insert(Key::String(String::from("kind")), Value::string(kind));
+    entries.insert(Key::String(String::from("backtrace")), Value::array(backtrace));
+
+    let (handler, taken) = pr.catch_error();
+
+    memo.truncate(handler.depth + 1);
+
+    // a mutation that failed leaves its container on the stack, the variable gets it back
+    for taken in taken {
+        if let Some(value) = stack.values().get(taken.stack) {
+            memo.assign(taken.name, value.clone()).ok();
+        }
+    }
+
+    stack.truncate(handler.stack);
+    stack.push(Value::map(entries));
+    env.take_blocked();
+
+    Ok(())
+}
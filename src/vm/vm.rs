@@ -1,13 +1,20 @@
 use crate::program::{Operation, Program, Value};
+use crate::vm::env::Env;
+use crate::engine::RuntimeError;
+use crate::vm::{CancelToken, Context, Limits};
 use crate::vm::operation::{get_op_executable};
+use crate::vm::rng::ScriptRng;
+use crate::vm::snapshot::{fits, program_hash, Snapshot};
+use crate::vm::tasks::{check_op, Scheduler, Tasks};
+use crate::vm::unwind::unwind;
 use std::collections::BTreeMap;
-use std::time::Duration;
-use std::{env, thread};
+use std::io::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 pub struct Stack(Vec<Value>);
 
 impl Stack {
-    pub fn new() -> Stack {
+    pub(crate) fn new() -> Stack {
         Stack(Vec::with_capacity(255))
     }
     pub fn push(&mut self, value: Value) {
@@ -16,52 +23,345 @@ impl Stack {
     pub fn pop(&mut self) -> Value {
         self.0.pop().unwrap()
     }
-    pub fn len(&self) -> usize {
+    pub(crate) fn len(&self) -> usize {
         self.0.len()
     }
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.0.truncate(len);
+    }
+    pub(crate) fn last(&self) -> Option<&Value> {
+        self.0.last()
+    }
+    pub(crate) fn last_mut(&mut self) -> Option<&mut Value> {
+        self.0.last_mut()
+    }
+    pub(crate) fn values(&self) -> &[Value] {
+        &self.0
+    }
+    pub(crate) fn from_values(values: Vec<Value>) -> Stack {
+        Stack(values)
+    }
+    pub(crate) fn into_values(self) -> Vec<Value> {
+        self.0
+    }
+}
+
+// one frame per flow invocation, lookup goes from the innermost frame to #MAIN
+pub struct Memo(Vec<BTreeMap<String, Value>>);
+
+impl Memo {
+    pub fn new() -> Memo {
+        Memo(vec![BTreeMap::new()])
+    }
+    pub fn enter(&mut self) {
+        self.0.push(BTreeMap::new());
+    }
+    pub fn leave(&mut self) {
+        self.0.pop();
+    }
+    pub fn frames(&self) -> &[BTreeMap<String, Value>] {
+        &self.0
+    }
+    pub fn from_frames(frames: Vec<BTreeMap<String, Value>>) -> Memo {
+        Memo(frames)
+    }
+    pub fn into_frames(self) -> Vec<BTreeMap<String, Value>> {
+        self.0
+    }
+    pub fn truncate(&mut self, depth: usize) {
+        self.0.truncate(depth);
+    }
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.iter().rev().find_map(|frame| frame.get(name))
+    }
+    pub fn define(&mut self, name: String, value: Value) -> Result<(), String> {
+        let frame = self.0.last_mut().unwrap();
+
+        if frame.contains_key(&name) {
+            return Err(format!("variable {name} already defined"));
+        }
+
+        frame.insert(name, value);
+
+        Ok(())
+    }
+    // moves the value out, so the only reference can be mutated without copying
+    pub fn take(&mut self, name: &str) -> Option<Value> {
+        self.0.iter_mut().rev().find_map(|frame| frame.get_mut(name)).map(|value| std::mem::replace(value, Value::Null))
+    }
+    // unlike define, overwrites the variable of the current frame
+    pub fn bind(&mut self, name: String, value: Value) {
+        self.0.last_mut().unwrap().insert(name, value);
+    }
+    pub fn assign(&mut self, name: String, value: Value) -> Result<(), String> {
+        match self.0.iter_mut().rev().find(|frame| frame.contains_key(&name)) {
+            Some(frame) => {
+                frame.insert(name, value);
+                Ok(())
+            }
+            None => Err(format!("variable {name} is not defined")),
+        }
+    }
 }
 
-pub type Memo = BTreeMap<String, Value>;
+// what run_for did with its budget
+#[derive(Clone, Debug, PartialEq)]
+pub enum Progress {
+    // the budget is spent, the run continues from here
+    Yielded,
+    // the entry flow yielded the value, the run continues after it
+    Produced(Value),
+    Finished(Value),
+}
 
 pub struct VM {
     debug: bool,
+    seed: Option<u64>,
+    scheduler: Scheduler,
+    env: Env,
+    // the started run, kept between resumes
+    stack: Stack,
+    memo: Memo,
+    running: bool,
 }
 
 impl VM {
     pub fn new() -> VM {
-        let debug = env::var("DEBUG").unwrap_or_else(|_e| "0".to_string());
-
         VM {
-            debug: debug.eq("1") || debug.eq("true")
+            debug: false,
+            seed: None,
+            scheduler: Scheduler::default(),
+            env: Env::new(),
+            stack: Stack::new(),
+            memo: Memo::new(),
+            running: false,
+        }
+    }
+    // every run with the same seed draws the same numbers
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+    // print writes to stdout and eprint to stderr unless they are replaced
+    pub fn set_output(&mut self, output: Box<dyn Write + Send>) {
+        self.env.out = Arc::new(Mutex::new(output));
+    }
+    pub fn set_error_output(&mut self, output: Box<dyn Write + Send>) {
+        self.env.err = Arc::new(Mutex::new(output));
+    }
+    // stays between runs, procedures reach it through the runtime
+    pub fn context(&mut self) -> MutexGuard<'_, Context> {
+        self.env.context()
+    }
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.env.limits = limits;
+    }
+    // spawned flows run on threads unless the deterministic scheduler is set
+    pub fn set_scheduler(&mut self, scheduler: Scheduler) {
+        self.scheduler = scheduler;
+    }
+    // cancels the current run from any thread
+    pub fn cancel_token(&self) -> CancelToken {
+        self.env.cancel.clone()
+    }
+    // runs the entry flow with the arguments and gives back what it returned
+    pub fn run(&mut self, pr: &mut Program, entry: &str, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.start(pr, entry, args)?;
+
+        match self.resume(pr, None)? {
+            Progress::Finished(value) => Ok(value),
+            Progress::Produced(_) => {
+                self.running = false;
+
+                Err(RuntimeError::new(format!("flow {entry} yields values, it has to be iterated")))
+            }
+            Progress::Yielded => unreachable!("a run without a budget does not yield"),
         }
     }
-    pub fn execute(&self, pr: &mut Program) {
-        let stack = &mut Stack::new();
-        let memo = &mut Memo::new();
+    // prepares the entry flow, resume executes it
+    pub fn start(&mut self, pr: &mut Program, entry: &str, args: Vec<Value>) -> Result<(), RuntimeError> {
+        if let Some(seed) = self.seed {
+            self.env.rng = ScriptRng::seed_from_u64(seed);
+        }
+
+        self.running = false;
+        self.env.start();
+        self.env.tasks.stop();
+        self.env.tasks = Arc::new(Tasks::new(self.scheduler));
+        pr.start_at(entry, args.len()).map_err(RuntimeError::Failed)?;
 
-        pr.jump_to_program_begin();
+        self.stack = Stack::new();
+        self.memo = Memo::new();
+
+        for arg in args {
+            self.stack.push(arg);
+        }
+
+        self.running = true;
+
+        Ok(())
+    }
+    // executes about budget ops and stops where the next call continues,
+    // a callback of map or filter is not interrupted, it finishes first
+    pub fn resume(&mut self, pr: &mut Program, budget: Option<u64>) -> Result<Progress, RuntimeError> {
+        if !self.running {
+            return Err(RuntimeError::Failed(String::from("there is no started run to resume")));
+        }
+
+        let until = budget.map(|ops| self.env.ops() + ops);
 
         loop {
+            if until.is_some_and(|until| self.env.ops() >= until) {
+                return Ok(Progress::Yielded);
+            }
+
             pr.next();
 
+            if pr.is_end() && pr.finish_block() {
+                self.memo.leave();
+            }
+
             if let Some(op) = pr.current() {
-                self.debug(op, stack);
+                let executed = self.debug(op).and_then(|_| get_op_executable(op.name)(pr, &mut self.stack, &mut self.memo, &mut self.env));
 
-                get_op_executable(op.name)(pr, stack, memo);
+                let checked = executed.and_then(|_| check_op(pr, &mut self.env, self.stack.len()));
+
+                if let Err(e) = checked.or_else(|e| unwind(pr, &mut self.stack, &mut self.memo, &mut self.env, e, 0)) {
+                    self.running = false;
+                    self.env.tasks.stop();
+
+                    return Err(self.env.error(e));
+                }
+                if let Some(value) = pr.take_yielded() {
+                    return Ok(Progress::Produced(value));
+                }
 
                 continue;
             }
 
             break;
         }
+
+        self.running = false;
+        self.env.tasks.stop();
+        self.env.flush().map_err(RuntimeError::Failed)?;
+
+        Ok(Progress::Finished(pr.take_exit()))
     }
+    // the paused run as bytes, restore continues it on a VM with the same program
+    pub fn snapshot(&self, pr: &Program) -> Result<Vec<u8>, RuntimeError> {
+        if !self.running {
+            return Err(RuntimeError::new("there is no started run to snapshot"));
+        }
+        if !self.env.tasks.is_empty() {
+            return Err(RuntimeError::new("a run with spawned flows or channels can't be snapshotted"));
+        }
+
+        let snapshot = Snapshot {
+            hash: program_hash(pr),
+            state: pr.state().clone(),
+            stack: self.stack.values().to_vec(),
+            frames: self.memo.frames().to_vec(),
+            rng: self.env.rng.state(),
+            ops: self.env.ops(),
+            made: self.env.made(),
+        };
+
+        Ok(snapshot.encode())
+    }
+    pub fn restore(&mut self, pr: &mut Program, bytes: &[u8]) -> Result<(), RuntimeError> {
+        let snapshot = Snapshot::decode(bytes).map_err(RuntimeError::Failed)?;
+
+        if snapshot.hash != program_hash(pr) {
+            return Err(RuntimeError::new("snapshot was taken from a different program"));
+        }
 
-    fn debug(&self, op: &Operation, stack: &Stack) {
+        if !fits(&snapshot.state, pr.len()) || snapshot.frames.is_empty() {
+            return Err(RuntimeError::new("snapshot does not fit the program"));
+        }
+
+        self.env.rng = ScriptRng::from_state(snapshot.rng).map_err(RuntimeError::Failed)?;
+        self.env.restore(snapshot.ops, snapshot.made);
+        pr.set_state(snapshot.state);
+        self.stack = Stack(snapshot.stack);
+        self.memo = Memo(snapshot.frames);
+        self.running = true;
+
+        Ok(())
+    }
+    #[cfg(test)]
+    pub fn take_memo(&mut self) -> Memo {
+        std::mem::replace(&mut self.memo, Memo::new())
+    }
+
+    // every op goes to the output before it runs, with the size of the stack
+    fn debug(&mut self, op: &Operation) -> Result<(), String> {
         if !self.debug {
-            return;
+            return Ok(());
         }
 
-        thread::sleep(Duration::from_millis(500));
-        println!("> {} {}", op.to_string(), stack.len());
+        let line = format!("> {} {}\n", op, self.stack.len());
+
+        self.env.write(&line)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::testing::{integer, run, run_on, Buffer};
+
+    #[test]
+    fn test_null_values() {
+        let memo = run("#MAIN() void
+call #NOTHING () $NOTHING
+var (is_null($NOTHING)) $NOTHING_IS_NULL
+var (null ?? 5) $DEFAULT
+var (3 ?? 5) $VALUE
+var (null = null) $SAME
+var (null = 0) $ZERO
+var (bool(null)) $FALSY
+#NOTHING() void
+print (\"nothing\")").unwrap();
+
+        assert_eq!(memo.get("$NOTHING"), Some(&Value::Null));
+        assert_eq!(memo.get("$NOTHING_IS_NULL"), Some(&Value::Boolean(true)));
+        assert_eq!(integer(&memo, "$DEFAULT"), 5);
+        assert_eq!(integer(&memo, "$VALUE"), 3);
+        assert_eq!(memo.get("$SAME"), Some(&Value::Boolean(true)));
+        assert_eq!(memo.get("$ZERO"), Some(&Value::Boolean(false)));
+        assert_eq!(memo.get("$FALSY"), Some(&Value::Boolean(false)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_arithmetic_on_null_is_an_error() {
+        let err = run("#MAIN() void\nvar (null + 1) $X").err().unwrap();
+        assert_eq!(err, "unable to null + 1");
+    }
+
+    #[test]
+    fn test_flow_arguments_keep_their_order() {
+        let memo = run("#MAIN() void
+call #PAIR (1, 2) $R
+#PAIR(int($A), int($B)) int
+return (($A * 10) + $B)").unwrap();
+
+        assert_eq!(integer(&memo, "$R"), 12);
+    }
+
+    #[test]
+    fn test_debug_writes_ops_to_the_output() {
+        let out = Buffer::default();
+        let mut vm = VM::new();
+        vm.set_output(Box::new(out.clone()));
+
+        run_on(&mut vm, "#MAIN() void\nvar (1) $X").unwrap();
+        assert_eq!(out.text(), "");
+
+        // the environment of the process does not turn it on, only the host does
+        vm.set_debug(true);
+        run_on(&mut vm, "#MAIN() void\nvar (1) $X").unwrap();
+        assert_eq!(out.text(), "> PUSH 1 0\n> VAR $X 1\n");
+    }
+}